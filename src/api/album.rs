@@ -0,0 +1,52 @@
+use itertools::Itertools;
+use rspotify::model::album::SimplifiedAlbum;
+
+use crate::api::{contains_ignore_case, Searchable};
+use crate::views::Popup;
+
+#[derive(Debug)]
+pub struct Album {
+    name: String,
+    uri: Option<String>,
+    artists: String,
+    release_date: Option<String>,
+}
+
+impl Album {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn uri(&self) -> Option<&str> {
+        self.uri.as_ref().map(|s| &s[..])
+    }
+
+    pub fn artists(&self) -> &str {
+        &self.artists
+    }
+
+    pub fn info_popup(&self) -> Popup {
+        Popup::new(vec![
+            format!("Album: {}", self.name),
+            format!("Artist: {}", self.artists),
+            format!("Released: {}", self.release_date.as_ref().map(|s| &s[..]).unwrap_or("<unknown>")),
+        ])
+    }
+}
+
+impl Searchable for Album {
+    fn matches(&self, pattern: &str) -> bool {
+        contains_ignore_case(&self.name, pattern) || contains_ignore_case(&self.artists, pattern)
+    }
+}
+
+impl From<SimplifiedAlbum> for Album {
+    fn from(a: SimplifiedAlbum) -> Album {
+        Album {
+            name: a.name,
+            uri: a.uri,
+            artists: a.artists.iter().map(|a| &a.name).join(", "),
+            release_date: a.release_date,
+        }
+    }
+}
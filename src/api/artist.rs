@@ -0,0 +1,45 @@
+use rspotify::model::artist::FullArtist;
+
+use crate::api::{contains_ignore_case, Searchable};
+use crate::views::Popup;
+
+#[derive(Debug)]
+pub struct Artist {
+    name: String,
+    uri: String,
+    genres: Vec<String>,
+}
+
+impl Artist {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    pub fn info_popup(&self) -> Popup {
+        let mut lines = vec![format!("Artist: {}", self.name)];
+        if !self.genres.is_empty() {
+            lines.push(format!("Genres: {}", self.genres.join(", ")));
+        }
+        Popup::new(lines)
+    }
+}
+
+impl Searchable for Artist {
+    fn matches(&self, pattern: &str) -> bool {
+        contains_ignore_case(&self.name, pattern)
+    }
+}
+
+impl From<FullArtist> for Artist {
+    fn from(a: FullArtist) -> Artist {
+        Artist {
+            name: a.name,
+            uri: a.uri,
+            genres: a.genres,
+        }
+    }
+}
@@ -0,0 +1,258 @@
+// Logging in to Spotify, and keeping the token fresh for as long as spotivi runs.
+// `spotivi login` saves a token with a refresh token in cache_path, and everything else
+// renews it from there instead of asking again
+
+use std::fs;
+use std::io::ErrorKind;
+use std::net::ToSocketAddrs;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use rspotify::client::Spotify;
+use rspotify::oauth2::{SpotifyOAuth, TokenInfo};
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use tokio::time;
+use url::Url;
+
+use crate::api::TransientError;
+use crate::config::Config;
+
+const SCOPES: &str = "streaming user-modify-playback-state user-read-playback-state playlist-read-private";
+const TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
+
+// Tokens are renewed this long before they run out, so a request never goes out with a stale one
+const REFRESH_MARGIN_SECS: i64 = 300;
+// How long to wait before trying again when renewing fails, like when the network is down
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+const NOT_LOGGED_IN: &str = "Not logged in to Spotify, run `spotivi login`";
+const LOGIN_AGAIN: &str = "run `spotivi login` to log in again";
+
+// The error Spotify's token endpoint sends back
+#[derive(Deserialize)]
+struct TokenError {
+    error: String,
+    error_description: Option<String>,
+}
+
+// The token every request uses, shared with the task that keeps it fresh
+#[derive(Clone)]
+pub struct Auth {
+    config: Arc<Config>,
+    token: Arc<Mutex<TokenInfo>>,
+}
+
+impl Auth {
+    // Reads the saved token, renewing it first if it's about to run out
+    pub async fn load(config: Arc<Config>) -> Result<Auth> {
+        check_credentials(&config)?;
+        let text = match fs::read_to_string(&config.cache_path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => bail!(NOT_LOGGED_IN),
+            Err(e) => bail!("Couldn't read {}: {}", config.cache_path.display(), e),
+        };
+        let token: TokenInfo = serde_json::from_str(&text)
+            .map_err(|_| anyhow!("{} isn't a saved login, {}", config.cache_path.display(), LOGIN_AGAIN))?;
+        if SCOPES.split(' ').any(|s| !token.scope.split(' ').any(|t| t == s)) {
+            bail!("The saved login doesn't allow everything spotivi needs, {}", LOGIN_AGAIN);
+        }
+
+        let auth = Auth {
+            config,
+            token: Arc::new(Mutex::new(token)),
+        };
+        auth.fresh_token().await?;
+        Ok(auth)
+    }
+
+    // Renews the token in the background whenever it gets close to running out
+    pub fn keep_fresh(&self) {
+        let auth = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let wait = {
+                    let token = auth.token.lock().await;
+                    Duration::from_secs(std::cmp::max(seconds_left(&token), 0) as u64)
+                };
+                time::delay_for(wait).await;
+                // Anything that needs the token in the meantime will see the error itself
+                if auth.fresh_token().await.is_err() {
+                    time::delay_for(RETRY_INTERVAL).await;
+                }
+            }
+        });
+    }
+
+    // A client with a token that's good for at least a few more minutes
+    pub async fn client(&self) -> Result<Spotify> {
+        let access_token = self.fresh_token().await?;
+        Ok(Spotify::default().access_token(&access_token).build())
+    }
+
+    async fn fresh_token(&self) -> Result<String> {
+        // Held while renewing, so only one renewal happens at a time
+        let mut token = self.token.lock().await;
+        if seconds_left(&token) <= 0 {
+            *token = refresh(&self.config, &token).await?;
+        }
+        Ok(token.access_token.clone())
+    }
+}
+
+// Seconds until the token should be renewed
+fn seconds_left(token: &TokenInfo) -> i64 {
+    token.expires_at.unwrap_or(0) - REFRESH_MARGIN_SECS - now()
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn check_credentials(config: &Config) -> Result<()> {
+    if config.api_client_id.is_empty() || config.api_client_secret.is_empty() {
+        bail!(
+            "api.client_id and api.client_secret need to be set in {}, see the README",
+            config.path.display(),
+        );
+    }
+    Ok(())
+}
+
+async fn refresh(config: &Config, token: &TokenInfo) -> Result<TokenInfo> {
+    let refresh_token = token.refresh_token.as_ref()
+        .ok_or_else(|| anyhow!("The saved login can't be renewed, {}", LOGIN_AGAIN))?;
+    let mut new_token = request_token(config, &[
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token),
+    ]).await?;
+    // Spotify only sometimes sends a new refresh token, otherwise the old one still works
+    if new_token.refresh_token.is_none() {
+        new_token.refresh_token = Some(refresh_token.clone());
+    }
+    save_token(config, &new_token)?;
+    Ok(new_token)
+}
+
+async fn request_token(config: &Config, form: &[(&str, &str)]) -> Result<TokenInfo> {
+    let response = reqwest::Client::new()
+        .post(TOKEN_URL)
+        .basic_auth(&config.api_client_id, Some(&config.api_client_secret))
+        .form(form)
+        .send()
+        .await
+        .map_err(|e| TransientError::Network(e.to_string()))?;
+
+    if response.status().is_server_error() {
+        return Err(TransientError::Server(response.status().as_u16()).into());
+    }
+    if !response.status().is_success() {
+        let status = response.status();
+        let error = response.json::<TokenError>().await.ok();
+        return Err(match error {
+            Some(ref e) if e.error == "invalid_client" => anyhow!(
+                "Spotify didn't accept api.client_id and api.client_secret, check them in {}",
+                config.path.display(),
+            ),
+            Some(ref e) if e.error == "invalid_grant" => {
+                anyhow!("Spotify didn't accept the saved login, {}", LOGIN_AGAIN)
+            }
+            Some(e) => anyhow!("Spotify refused to log in: {}", e.error_description.unwrap_or(e.error)),
+            None => anyhow!("Spotify refused to log in: {}", status),
+        });
+    }
+
+    let mut token: TokenInfo = response.json().await
+        .map_err(|e| anyhow!("Spotify sent back a token that couldn't be read: {}", e))?;
+    token.expires_at = Some(now() + token.expires_in as i64);
+    Ok(token)
+}
+
+fn save_token(config: &Config, token: &TokenInfo) -> Result<()> {
+    fs::write(&config.cache_path, serde_json::to_string(token)?)
+        .map_err(|e| anyhow!("Couldn't save the login to {}: {}", config.cache_path.display(), e))
+}
+
+// Sends the user to Spotify to log in, and waits for Spotify to send the browser back to
+// redirect_uri, which has to be on this machine
+pub async fn login(config: &Config) -> Result<()> {
+    check_credentials(config)?;
+    let redirect = Url::parse(&config.redirect_uri)
+        .map_err(|e| anyhow!("api.redirect_uri isn't a url: {}", e))?;
+    let host = redirect.host_str().unwrap_or("");
+    if redirect.scheme() != "http" || !["localhost", "127.0.0.1", "[::1]"].contains(&host) {
+        bail!(
+            "api.redirect_uri has to be an http://localhost address to log in from here, not {}",
+            config.redirect_uri,
+        );
+    }
+    let port = redirect.port_or_known_default().unwrap_or(80);
+    let addr = (host.trim_matches(|c| c == '[' || c == ']'), port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("Couldn't find {}", host))?;
+    let mut listener = TcpListener::bind(addr).await
+        .map_err(|e| anyhow!("Couldn't listen on {} for the login: {}", addr, e))?;
+
+    let oauth = SpotifyOAuth::default()
+        .scope(SCOPES)
+        .client_id(&config.api_client_id)
+        .client_secret(&config.api_client_secret)
+        .redirect_uri(&config.redirect_uri)
+        .build();
+    println!("Open this link to log in to Spotify:\n\n{}\n", oauth.get_authorize_url(None, None));
+    println!("The redirect uri {} has to be allowed in your Spotify app's settings.", config.redirect_uri);
+
+    let code = loop {
+        let (mut stream, _) = listener.accept().await?;
+        let (reader, mut writer) = stream.split();
+        let mut request_line = String::new();
+        BufReader::new(reader).read_line(&mut request_line).await?;
+
+        // Like "GET /callback?code=...&state=... HTTP/1.1"
+        let path = request_line.split(' ').nth(1).unwrap_or("");
+        let url = match redirect.join(path) {
+            Ok(url) if url.path() == redirect.path() => url,
+            // Like the browser asking for favicon.ico
+            _ => {
+                writer.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n").await.ok();
+                continue;
+            }
+        };
+        let param = |name: &str| url.query_pairs().find(|(k, _)| k == name).map(|(_, v)| v.into_owned());
+
+        // The state is random each time, so an old or forged redirect can't log in
+        let result = if param("state").as_ref() != Some(&oauth.state) {
+            Err(anyhow!("That login wasn't started by this `spotivi login`, try again"))
+        } else if let Some(error) = param("error") {
+            Err(anyhow!("Spotify didn't log in: {}", error))
+        } else {
+            param("code").ok_or_else(|| anyhow!("Spotify didn't send back a login code"))
+        };
+        let page = match result {
+            Ok(_) => "Logged in to spotivi, this tab can be closed now.".to_owned(),
+            Err(ref e) => e.to_string(),
+        };
+        writer.write_all(format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            page.len(),
+            page,
+        ).as_bytes()).await.ok();
+        break result?;
+    };
+
+    let token = request_token(config, &[
+        ("grant_type", "authorization_code"),
+        ("code", &code),
+        ("redirect_uri", &config.redirect_uri),
+    ]).await?;
+    save_token(config, &token)?;
+    println!("Logged in.");
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use std::fmt;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use rspotify::senum::RepeatState;
+
+use crate::api::{
+    Device,
+    Paged,
+    PlaybackState,
+    Playlist,
+    PlaylistSummary,
+    PlaylistTrack,
+    SearchKind,
+    SearchPage,
+};
+
+// Everything spotivi asks of a music service. SpotifyApi is the real one
+#[async_trait]
+pub trait MusicBackend: Send + Sync {
+    // `page` is a page number, not an offset
+    async fn get_playlists(&self, page: u32) -> Result<Paged<PlaylistSummary>>;
+    async fn get_playlist(&self, id: &str) -> Result<Playlist>;
+    async fn get_playlist_tracks(&self, owner_id: &str, id: &str, offset: u32) -> Result<Paged<PlaylistTrack>>;
+    async fn search(&self, query: &str, kind: SearchKind, offset: u32) -> Result<SearchPage>;
+    async fn get_devices(&self) -> Result<Vec<Device>>;
+
+    fn device_id(&self) -> Option<&str>;
+
+    // Prefers the device picked last time, then whichever one is already playing.
+    // Does nothing if there's already a device
+    fn choose_device(&mut self, devices: &[Device], saved_id: Option<String>);
+
+    async fn transfer_playback(&mut self, id: String, play: bool) -> Result<()>;
+    async fn toggle_playback(&mut self) -> Result<()>;
+    async fn resume_playback(&mut self) -> Result<()>;
+    async fn pause_playback(&mut self) -> Result<()>;
+    async fn playback_status(&self) -> Result<Option<PlaybackState>>;
+
+    // Lets toggle_playback know what's going on after polling playback_status
+    fn set_playing(&mut self, playing: bool);
+
+    async fn next_track(&self) -> Result<()>;
+    async fn previous_track(&self) -> Result<()>;
+    async fn set_volume(&self, volume_percent: u8) -> Result<()>;
+    async fn set_shuffle(&self, shuffle: bool) -> Result<()>;
+    async fn set_repeat(&self, repeat: RepeatState) -> Result<()>;
+    async fn seek(&self, position_ms: u32) -> Result<()>;
+
+    // Playing inside a context (a playlist, album or artist) lets playback continue
+    // through the rest of it, starting from the track at `position`
+    async fn play_context(&mut self, context_uri: String, position: Option<u32>) -> Result<()>;
+    async fn play_from_uri(&mut self, uri: String) -> Result<()>;
+}
+
+// Failures that might go away on their own, so the request is worth sending again.
+// Backends return these inside their anyhow errors for the scheduler to find
+#[derive(Debug)]
+pub enum TransientError {
+    // Too many requests, and how long to wait if the service said
+    RateLimited(Option<Duration>),
+    // A problem on the service's end, like a 503
+    Server(u16),
+    // The service couldn't be reached
+    Network(String),
+}
+
+impl fmt::Display for TransientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransientError::RateLimited(_) => write!(f, "Too many requests, Spotify asked to slow down"),
+            TransientError::Server(status) => write!(f, "Spotify had a problem ({})", status),
+            TransientError::Network(e) => write!(f, "Couldn't reach Spotify: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TransientError {}
+
+// Shared by the backends, so they pick the same device
+pub fn choose_device_id(devices: &[Device], saved_id: Option<String>) -> Option<String> {
+    devices.iter()
+        .find(|d| Some(d.id()) == saved_id.as_ref().map(|s| &s[..]))
+        .or_else(|| devices.iter().find(|d| d.is_active()))
+        .or_else(|| devices.first())
+        .map(|d| d.id().to_owned())
+}
@@ -1,4 +1,4 @@
-use std::io::{stdout, Write};
+use std::io::Write;
 
 use anyhow::Result;
 use crossterm::{cursor, queue, style};
@@ -8,12 +8,39 @@ use crate::{
     api::Paged,
     app::Action,
     keybindings::KeyBinding,
+    store::Store,
+    views::Output,
 };
 
+// Things that can be found with `/` in a list
+pub trait Searchable {
+    // `pattern` is already lowercase
+    fn matches(&self, pattern: &str) -> bool;
+}
+
+pub fn contains_ignore_case(s: &str, pattern: &str) -> bool {
+    s.to_lowercase().contains(pattern)
+}
+
+// A motion that's waiting on the next page to load
+#[derive(Clone, Copy, Debug)]
+enum Pending {
+    // The direction of a find
+    Find(bool),
+    // The line to go to, or the last one
+    Line(Option<usize>),
+}
+
 #[derive(Debug)]
 pub struct Cursor {
     index: usize,
     scroll_offset: usize,
+    pattern: Option<String>,
+    pending: Option<Pending>,
+    // Where the cursor was when `/` was pressed, and the pattern before it
+    find_origin: Option<(usize, usize, Option<String>)>,
+    // The count typed before the key being handled, like the 10 in `10j`
+    count: Option<usize>,
 }
 
 impl Cursor {
@@ -21,9 +48,17 @@ impl Cursor {
         Cursor {
             index: 0,
             scroll_offset: 0,
+            pattern: None,
+            pending: None,
+            find_origin: None,
+            count: None,
         }
     }
 
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
     pub fn get<'a, T: 'a>(&self, index: usize, items: &'a [T]) -> Option<&'a T> {
         items.get(index)
     }
@@ -32,41 +67,98 @@ impl Cursor {
         items.get(self.index)
     }
 
-    fn select_next(&mut self, len: usize, height: u16) {
+    pub fn set_count(&mut self, count: Option<usize>) {
+        self.count = count;
+    }
+
+    fn move_by(&mut self, delta: isize, len: usize, height: u16) {
         if len != 0 {
-            self.index = std::cmp::min(len - 1, self.index + 1);
-            if self.index - self.scroll_offset >= height as usize {
-                self.scroll_offset += 1;
-            }
+            self.select(clamp(self.index as isize + delta, len - 1), height);
         } else {
-            // TODO: scroll_offset???
             self.index = 0;
+            self.scroll_offset = 0;
         }
     }
 
-    fn select_prev(&mut self) {
-        if self.index != 0 {
-            self.index -= 1;
-            if self.index < self.scroll_offset {
-                self.scroll_offset -= 1;
-            }
+    // Moves the view and the cursor together, like vim's ctrl-d and ctrl-f
+    fn scroll(&mut self, delta: isize, len: usize, height: u16) {
+        if len == 0 {
+            return;
         }
+        let max_offset = len.saturating_sub(std::cmp::max(height, 1) as usize);
+        self.scroll_offset = clamp(self.scroll_offset as isize + delta, max_offset);
+        self.select(clamp(self.index as isize + delta, len - 1), height);
     }
 
-    pub fn queue_draw<'a, T: 'a>(&self, items: impl Iterator<Item=&'a T>, height: usize, display_item: impl Fn(&'a T) -> Result<()>) -> Result<()> {
+    // Like vim's H, M and L, which only move within what's on screen
+    fn select_on_screen(&mut self, position: ScreenPosition, len: usize, height: u16) {
+        if len == 0 {
+            return;
+        }
+        let count = self.count.unwrap_or(1);
+        let last = std::cmp::min(len, self.scroll_offset + std::cmp::max(height, 1) as usize) - 1;
+        let index = match position {
+            ScreenPosition::Top => self.scroll_offset + count - 1,
+            ScreenPosition::Middle => (self.scroll_offset + last) / 2,
+            ScreenPosition::Bottom => last.saturating_sub(count - 1),
+        };
+        self.select(std::cmp::max(self.scroll_offset, std::cmp::min(index, last)), height);
+    }
+
+    // `G` goes to the end of everything, not just what's loaded, so this keeps loading pages.
+    // With a count it's the line to go to, starting from 1
+    fn go_to_line<T>(&mut self, store: &Store, paged: &Paged<T>, height: u16, line: Option<usize>) -> Option<Action> {
+        let len = paged.len();
+        let loaded = line.map(|l| l <= len).unwrap_or(false);
+        if !loaded && paged.next_page().is_some() {
+            self.pending = Some(Pending::Line(line));
+            paged.load_next(store);
+        }
+
+        if len != 0 {
+            let index = line.map(|l| l.saturating_sub(1)).unwrap_or(len - 1);
+            self.select(std::cmp::min(index, len - 1), height);
+        }
+        Some(Action::Redraw)
+    }
+
+    // Scrolls as little as possible to keep `index` on screen
+    fn select(&mut self, index: usize, height: u16) {
+        let height = std::cmp::max(height, 1) as usize;
+        self.index = index;
+        if index < self.scroll_offset {
+            self.scroll_offset = index;
+        } else if index >= self.scroll_offset + height {
+            self.scroll_offset = index + 1 - height;
+        }
+    }
+
+    pub fn queue_draw<'a, T: Searchable + 'a>(
+        &self,
+        output: &mut Output,
+        items: impl Iterator<Item=&'a T>,
+        height: usize,
+        display_item: impl Fn(&mut Output, &'a T) -> Result<()>,
+    ) -> Result<()> {
         for (i, t) in items.enumerate().skip(self.scroll_offset).take(height) {
             if self.is_highlighted(i) {
                 queue!(
-                    stdout(),
+                    output,
                     style::SetAttribute(style::Attribute::Reverse),
                 )?;
             }
+            if self.pattern.as_ref().map(|p| t.matches(p)).unwrap_or(false) {
+                queue!(
+                    output,
+                    style::SetAttribute(style::Attribute::Underlined),
+                )?;
+            }
 
             // TODO: maybe pass more info, like the index and if it's highlighted and stuff
-            display_item(t)?;
+            display_item(output, t)?;
 
             queue!(
-                stdout(),
+                output,
                 style::SetAttribute(style::Attribute::Reset),
                 cursor::MoveToNextLine(1),
             )?;
@@ -80,24 +172,113 @@ impl Cursor {
         self.index == i
     }
 
-    pub fn receive_input<T>(&mut self, input: KeyBinding, paged: &Paged<T>, height: u16) -> Option<Action> {
-        match input {
-            KeyBinding::Up => {
-                self.select_prev();
-                Some(Action::Redraw)
+    pub fn start_find(&mut self) {
+        self.find_origin = Some((self.index, self.scroll_offset, self.pattern.clone()));
+    }
+
+    // Each time the pattern changes the search starts over from where `/` was pressed
+    pub fn update_find(&mut self, pattern: &str) {
+        if let Some((index, scroll_offset, _)) = self.find_origin {
+            self.index = index;
+            self.scroll_offset = scroll_offset;
+        }
+        self.pattern = Some(pattern.to_lowercase()).filter(|p| !p.is_empty());
+        self.pending = None;
+    }
+
+    // When the find is cancelled the cursor and the old pattern come back
+    pub fn end_find(&mut self, keep: bool) {
+        if let Some((index, scroll_offset, pattern)) = self.find_origin.take() {
+            if !keep {
+                self.index = index;
+                self.scroll_offset = scroll_offset;
+                self.pattern = pattern;
+                self.pending = None;
             }
-            KeyBinding::Down => {
-                self.select_next(paged.len(), height);
-                if self.needs_next_page(paged.len()) {
-                    paged.load_next();
-                }
-                Some(Action::Redraw)
+        }
+    }
+
+    // Call when more items have loaded, in case a find or `G` was waiting on them
+    pub fn resume<T: Searchable>(&mut self, store: &Store, paged: &Paged<T>, height: u16) -> Option<Action> {
+        match self.pending.take()? {
+            Pending::Find(forward) => self.find(store, paged, height, forward),
+            Pending::Line(line) => self.go_to_line(store, paged, height, line),
+        }
+    }
+
+    fn find<T: Searchable>(&mut self, store: &Store, paged: &Paged<T>, height: u16, forward: bool) -> Option<Action> {
+        let pattern = self.pattern.as_ref()?;
+        let items = paged.items();
+        let matches = |i: &usize| items[*i].matches(pattern);
+
+        let found = if forward {
+            (self.index + 1..items.len()).find(matches)
+        } else {
+            (0..std::cmp::min(self.index, items.len())).rev().find(matches)
+        };
+        let found = match found {
+            Some(i) => Some(i),
+            // The match might be on a page that isn't loaded yet
+            None if forward && paged.next_page().is_some() => {
+                self.pending = Some(Pending::Find(forward));
+                paged.load_next(store);
+                return None;
             }
+            // Wrap around, like vim
+            None if forward => (0..std::cmp::min(self.index + 1, items.len())).find(matches),
+            None => (self.index..items.len()).rev().find(matches),
+        };
+
+        if let Some(i) = found {
+            self.select(i, height);
+        }
+        Some(Action::Redraw)
+    }
+
+    pub fn receive_input<T: Searchable>(&mut self, store: &Store, input: KeyBinding, paged: &Paged<T>, height: u16) -> Option<Action> {
+        let len = paged.len();
+        let count = self.count.unwrap_or(1) as isize;
+        let half_page = std::cmp::max(height / 2, 1) as isize;
+        let page = std::cmp::max(height, 1) as isize;
+        // Whatever was waiting on a page doesn't matter anymore once the cursor moves
+        self.pending = None;
+
+        match input {
+            KeyBinding::Up => self.move_by(-count, len, height),
+            KeyBinding::Down => self.move_by(count, len, height),
+            // Unlike the others, a count here is how far to scroll each time, like vim
+            KeyBinding::HalfPageDown => self.scroll(self.count.map(|c| c as isize).unwrap_or(half_page), len, height),
+            KeyBinding::HalfPageUp => self.scroll(-self.count.map(|c| c as isize).unwrap_or(half_page), len, height),
+            KeyBinding::PageDown => self.scroll(count * page, len, height),
+            KeyBinding::PageUp => self.scroll(-count * page, len, height),
+            KeyBinding::ScreenTop => self.select_on_screen(ScreenPosition::Top, len, height),
+            KeyBinding::ScreenMiddle => self.select_on_screen(ScreenPosition::Middle, len, height),
+            KeyBinding::ScreenBottom => self.select_on_screen(ScreenPosition::Bottom, len, height),
+            KeyBinding::Top if self.count.is_none() => self.move_by(-(self.index as isize), len, height),
+            KeyBinding::Top | KeyBinding::Bottom => return self.go_to_line(store, paged, height, self.count),
+            KeyBinding::FindNext => return self.find(store, paged, height, true),
+            KeyBinding::FindPrev => return self.find(store, paged, height, false),
             _ => return None,
         }
+
+        if self.needs_next_page(len) {
+            paged.load_next(store);
+        }
+        Some(Action::Redraw)
     }
 
     fn needs_next_page(&self, len: usize) -> bool {
-        (len - self.index) <= PAGE_SIZE as usize / 4
+        len.saturating_sub(self.index) <= PAGE_SIZE as usize / 4
     }
 }
+
+#[derive(Clone, Copy)]
+enum ScreenPosition {
+    Top,
+    Middle,
+    Bottom,
+}
+
+fn clamp(n: isize, max: usize) -> usize {
+    std::cmp::min(std::cmp::max(n, 0) as usize, max)
+}
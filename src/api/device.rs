@@ -0,0 +1,52 @@
+use rspotify::model::device::Device as D;
+
+use crate::api::{contains_ignore_case, Searchable};
+
+#[derive(Debug)]
+pub struct Device {
+    id: String,
+    name: String,
+    device_type: String,
+    volume_percent: u32,
+    is_active: bool,
+}
+
+impl Device {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn device_type(&self) -> &str {
+        &self.device_type
+    }
+
+    pub fn volume_percent(&self) -> u32 {
+        self.volume_percent
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.is_active
+    }
+}
+
+impl Searchable for Device {
+    fn matches(&self, pattern: &str) -> bool {
+        contains_ignore_case(&self.name, pattern)
+    }
+}
+
+impl From<D> for Device {
+    fn from(d: D) -> Device {
+        Device {
+            id: d.id,
+            name: d.name,
+            device_type: format!("{:?}", d._type),
+            volume_percent: d.volume_percent,
+            is_active: d.is_active,
+        }
+    }
+}
@@ -0,0 +1,578 @@
+// A backend that never touches the network, for tests. Its playlists and devices are scripted
+// up front, and the playback calls change its own state the way Spotify would
+
+use std::cmp::min;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use rspotify::model::context::CurrentlyPlaybackContext;
+use rspotify::model::device::Device as SpotifyDevice;
+use rspotify::model::page::Page;
+use rspotify::model::playlist::{FullPlaylist, PlaylistTrack as SpotifyPlaylistTrack, SimplifiedPlaylist};
+use rspotify::model::track::FullTrack;
+use rspotify::senum::RepeatState;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::api::{
+    choose_device_id,
+    contains_ignore_case,
+    Device,
+    MusicBackend,
+    Paged,
+    PageId,
+    PlaybackState,
+    Playlist,
+    PlaylistSummary,
+    PlaylistTrack,
+    SearchKind,
+    SearchPage,
+    TransientError,
+    PAGE_SIZE,
+};
+
+const OWNER_ID: &str = "fake_user";
+const TRACK_DURATION_MS: u32 = 180_000;
+
+#[derive(Clone)]
+struct FakeTrack {
+    id: String,
+    name: String,
+    artist: String,
+    album: String,
+}
+
+impl FakeTrack {
+    fn uri(&self) -> String {
+        format!("spotify:track:{}", self.id)
+    }
+}
+
+struct FakePlaylist {
+    id: String,
+    name: String,
+    tracks: Vec<FakeTrack>,
+}
+
+impl FakePlaylist {
+    fn uri(&self) -> String {
+        format!("spotify:playlist:{}", self.id)
+    }
+
+    // Scripted playlists never change while a test runs, so the tracks are enough to tell
+    // two versions apart
+    fn snapshot_id(&self) -> String {
+        self.tracks.iter().map(|t| &t.name[..]).collect::<Vec<_>>().join("/")
+    }
+}
+
+struct FakeDevice {
+    id: String,
+    name: String,
+}
+
+// What Spotify itself would know, as opposed to what the client has been told
+struct PlayerState {
+    active_device: Option<String>,
+    // The tracks next and previous move through, and the one playing
+    queue: Vec<FakeTrack>,
+    position: usize,
+    playing: bool,
+    progress_ms: u32,
+    volume_percent: u32,
+    shuffle: bool,
+    repeat: RepeatState,
+}
+
+pub struct FakeBackend {
+    playlists: Vec<FakePlaylist>,
+    devices: Vec<FakeDevice>,
+    device_id: Option<String>,
+    playing: bool,
+    unreachable: bool,
+    // How many more times fetching the playlists fails like Spotify being overloaded
+    busy: Mutex<u32>,
+    state: Mutex<PlayerState>,
+    calls: Arc<Mutex<Vec<String>>>,
+    fetches: Arc<Mutex<Vec<String>>>,
+}
+
+impl FakeBackend {
+    pub fn new() -> FakeBackend {
+        FakeBackend {
+            playlists: Vec::new(),
+            devices: Vec::new(),
+            device_id: None,
+            playing: false,
+            unreachable: false,
+            busy: Mutex::new(0),
+            state: Mutex::new(PlayerState {
+                active_device: None,
+                queue: Vec::new(),
+                position: 0,
+                playing: false,
+                progress_ms: 0,
+                volume_percent: 50,
+                shuffle: false,
+                repeat: RepeatState::Off,
+            }),
+            calls: Arc::new(Mutex::new(Vec::new())),
+            fetches: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    // Tracks are (title, artist), and get ids like "<playlist id>-<index>"
+    pub fn playlist(mut self, id: &str, name: &str, tracks: &[(&str, &str)]) -> FakeBackend {
+        let tracks = tracks.iter()
+            .enumerate()
+            .map(|(i, &(title, artist))| FakeTrack {
+                id: format!("{}-{}", id, i),
+                name: title.to_owned(),
+                artist: artist.to_owned(),
+                album: name.to_owned(),
+            })
+            .collect();
+        self.playlists.push(FakePlaylist {
+            id: id.to_owned(),
+            name: name.to_owned(),
+            tracks,
+        });
+        self
+    }
+
+    // For paging, a playlist of `count` tracks called "Track 0", "Track 1" and so on
+    pub fn numbered_playlist(self, id: &str, name: &str, count: usize) -> FakeBackend {
+        let titles: Vec<String> = (0..count).map(|i| format!("Track {}", i)).collect();
+        let tracks: Vec<(&str, &str)> = titles.iter().map(|t| (&t[..], "Nobody")).collect();
+        self.playlist(id, name, &tracks)
+    }
+
+    // At most one device should be active, which is the one already playing
+    pub fn device(mut self, id: &str, name: &str, active: bool) -> FakeBackend {
+        self.devices.push(FakeDevice {
+            id: id.to_owned(),
+            name: name.to_owned(),
+        });
+        if active {
+            self.state.get_mut().unwrap().active_device = Some(id.to_owned());
+        }
+        self
+    }
+
+    // Like the network being down, so loading the playlists fails the way it would
+    pub fn unreachable(mut self) -> FakeBackend {
+        self.unreachable = true;
+        self
+    }
+
+    // Like Spotify being overloaded, so the first `times` fetches of the playlists fail but it
+    // can still be reached
+    pub fn busy(self, times: u32) -> FakeBackend {
+        *self.busy.lock().unwrap() = times;
+        self
+    }
+
+    // Everything that changed playback, like "play spotify:track:p-0", oldest first.
+    // Shared, so it can still be read once the backend is boxed up
+    pub fn calls(&self) -> Arc<Mutex<Vec<String>>> {
+        Arc::clone(&self.calls)
+    }
+
+    // Every playlist fetch, like "playlist road", so tests can tell what came from the cache
+    pub fn fetches(&self) -> Arc<Mutex<Vec<String>>> {
+        Arc::clone(&self.fetches)
+    }
+
+    fn log_fetch(&self, fetch: String) {
+        self.fetches.lock().unwrap().push(fetch);
+    }
+
+    fn log(&self, call: String) {
+        self.calls.lock().unwrap().push(call);
+    }
+
+    // Playback goes to the chosen device, or whichever one is active if none was chosen
+    fn start_playing(&self, state: &mut PlayerState) -> Result<()> {
+        if let Some(ref id) = self.device_id {
+            state.active_device = Some(id.clone());
+        }
+        if state.active_device.is_none() {
+            bail!("No active device");
+        }
+        state.playing = !state.queue.is_empty();
+        Ok(())
+    }
+
+    fn device_json(&self, d: &FakeDevice, state: &PlayerState) -> Value {
+        let active = state.active_device.as_ref() == Some(&d.id);
+        json!({
+            "id": d.id,
+            "is_active": active,
+            "is_restricted": false,
+            "name": d.name,
+            "type": "Computer",
+            "volume_percent": if active { state.volume_percent } else { 100 },
+        })
+    }
+}
+
+#[async_trait]
+impl MusicBackend for FakeBackend {
+    async fn get_playlists(&self, page: u32) -> Result<Paged<PlaylistSummary>> {
+        if self.unreachable {
+            return Err(TransientError::Network("connection refused".to_owned()).into());
+        }
+        {
+            let mut busy = self.busy.lock().unwrap();
+            if *busy > 0 {
+                *busy -= 1;
+                return Err(TransientError::Server(503).into());
+            }
+        }
+        self.log_fetch(format!("playlists {}", page));
+        let offset = (page * PAGE_SIZE) as usize;
+        let items: Vec<SimplifiedPlaylist> = self.playlists.iter()
+            .skip(offset)
+            .take(PAGE_SIZE as usize)
+            .map(|p| from_json(summary_json(p)))
+            .collect();
+        Ok(Paged::from(page_of(items, offset, self.playlists.len()), PageId::Playlists))
+    }
+
+    async fn get_playlist(&self, id: &str) -> Result<Playlist> {
+        let p = self.playlists.iter()
+            .find(|p| p.id == id)
+            .ok_or_else(|| anyhow!("No playlist with id {}", id))?;
+        self.log_fetch(format!("playlist {}", id));
+        let tracks: Vec<Value> = p.tracks.iter().take(PAGE_SIZE as usize).map(playlist_track_json).collect();
+        let next = Some("fake://next").filter(|_| p.tracks.len() > PAGE_SIZE as usize);
+        Ok(from_json::<FullPlaylist>(json!({
+            "collaborative": false,
+            "description": "",
+            "external_urls": {},
+            "followers": null,
+            "href": "",
+            "id": p.id,
+            "images": [],
+            "name": p.name,
+            "owner": user_json(),
+            "public": true,
+            "snapshot_id": p.snapshot_id(),
+            "tracks": {
+                "href": "",
+                "items": tracks,
+                "limit": PAGE_SIZE,
+                "next": next,
+                "offset": 0,
+                "previous": null,
+                "total": p.tracks.len(),
+            },
+            "type": "playlist",
+            "uri": p.uri(),
+        })).into())
+    }
+
+    async fn get_playlist_tracks(&self, _owner_id: &str, id: &str, offset: u32) -> Result<Paged<PlaylistTrack>> {
+        let p = self.playlists.iter()
+            .find(|p| p.id == id)
+            .ok_or_else(|| anyhow!("No playlist with id {}", id))?;
+        self.log_fetch(format!("tracks {} at {}", id, offset));
+        let offset = offset as usize;
+        let items: Vec<SpotifyPlaylistTrack> = p.tracks.iter()
+            .skip(offset)
+            .take(PAGE_SIZE as usize)
+            .map(|t| from_json(playlist_track_json(t)))
+            .collect();
+        Ok(Paged::from(page_of(items, offset, p.tracks.len()), PageId::Playlist(id.to_owned())))
+    }
+
+    async fn search(&self, query: &str, kind: SearchKind, offset: u32) -> Result<SearchPage> {
+        let page_id = PageId::Search { query: query.to_owned(), kind };
+        let offset = offset as usize;
+        Ok(match kind {
+            SearchKind::Tracks => {
+                let found: Vec<&FakeTrack> = self.playlists.iter()
+                    .flat_map(|p| p.tracks.iter())
+                    .filter(|t| contains_ignore_case(&t.name, query) || contains_ignore_case(&t.artist, query))
+                    .collect();
+                let items: Vec<FullTrack> = found.iter()
+                    .skip(offset)
+                    .take(PAGE_SIZE as usize)
+                    .map(|t| from_json(track_json(t)))
+                    .collect();
+                SearchPage::Tracks(Paged::from(page_of(items, offset, found.len()), page_id))
+            }
+            SearchKind::Playlists => {
+                let found: Vec<&FakePlaylist> = self.playlists.iter()
+                    .filter(|p| contains_ignore_case(&p.name, query))
+                    .collect();
+                let items: Vec<SimplifiedPlaylist> = found.iter()
+                    .skip(offset)
+                    .take(PAGE_SIZE as usize)
+                    .map(|p| from_json(summary_json(p)))
+                    .collect();
+                SearchPage::Playlists(Paged::from(page_of(items, offset, found.len()), page_id))
+            }
+            // Nothing scripted has albums or artists of its own
+            SearchKind::Albums => SearchPage::Albums(Paged::from_items(Vec::new(), page_id)),
+            SearchKind::Artists => SearchPage::Artists(Paged::from_items(Vec::new(), page_id)),
+        })
+    }
+
+    async fn get_devices(&self) -> Result<Vec<Device>> {
+        let state = self.state.lock().unwrap();
+        Ok(self.devices.iter()
+            .map(|d| from_json::<SpotifyDevice>(self.device_json(d, &state)).into())
+            .collect())
+    }
+
+    fn device_id(&self) -> Option<&str> {
+        self.device_id.as_ref().map(|s| &s[..])
+    }
+
+    fn choose_device(&mut self, devices: &[Device], saved_id: Option<String>) {
+        if self.device_id.is_none() {
+            self.device_id = choose_device_id(devices, saved_id);
+        }
+    }
+
+    async fn transfer_playback(&mut self, id: String, play: bool) -> Result<()> {
+        if !self.devices.iter().any(|d| d.id == id) {
+            bail!("No device with id {}", id);
+        }
+        self.log(format!("transfer {}", id));
+        {
+            let mut state = self.state.lock().unwrap();
+            state.active_device = Some(id.clone());
+            if play {
+                state.playing = !state.queue.is_empty();
+            }
+        }
+        self.device_id = Some(id);
+        self.playing = play;
+        Ok(())
+    }
+
+    async fn toggle_playback(&mut self) -> Result<()> {
+        if self.playing {
+            self.pause_playback().await
+        } else {
+            self.resume_playback().await
+        }
+    }
+
+    async fn resume_playback(&mut self) -> Result<()> {
+        self.log("resume".to_owned());
+        self.start_playing(&mut self.state.lock().unwrap())?;
+        self.playing = true;
+        Ok(())
+    }
+
+    async fn pause_playback(&mut self) -> Result<()> {
+        self.log("pause".to_owned());
+        self.state.lock().unwrap().playing = false;
+        self.playing = false;
+        Ok(())
+    }
+
+    async fn playback_status(&self) -> Result<Option<PlaybackState>> {
+        let state = self.state.lock().unwrap();
+        let device = match state.active_device {
+            Some(ref id) => self.devices.iter().find(|d| &d.id == id),
+            None => None,
+        };
+        let (device, track) = match (device, state.queue.get(state.position)) {
+            (Some(d), Some(t)) => (d, t),
+            _ => return Ok(None),
+        };
+        let context = from_json::<CurrentlyPlaybackContext>(json!({
+            "device": self.device_json(device, &state),
+            "repeat_state": serde_json::to_value(state.repeat)?,
+            "shuffle_state": state.shuffle,
+            "context": null,
+            "timestamp": 0,
+            "progress_ms": state.progress_ms,
+            "is_playing": state.playing,
+            "item": track_json(track),
+            "currently_playing_type": "track",
+            "actions": { "disallows": {} },
+        }));
+        Ok(Some(context.into()))
+    }
+
+    fn set_playing(&mut self, playing: bool) {
+        self.playing = playing;
+    }
+
+    async fn next_track(&self) -> Result<()> {
+        self.log("next".to_owned());
+        let mut state = self.state.lock().unwrap();
+        if state.position + 1 < state.queue.len() {
+            state.position += 1;
+        }
+        state.progress_ms = 0;
+        Ok(())
+    }
+
+    async fn previous_track(&self) -> Result<()> {
+        self.log("previous".to_owned());
+        let mut state = self.state.lock().unwrap();
+        state.position = state.position.saturating_sub(1);
+        state.progress_ms = 0;
+        Ok(())
+    }
+
+    async fn set_volume(&self, volume_percent: u8) -> Result<()> {
+        self.log(format!("volume {}", volume_percent));
+        self.state.lock().unwrap().volume_percent = min(volume_percent, 100) as u32;
+        Ok(())
+    }
+
+    async fn set_shuffle(&self, shuffle: bool) -> Result<()> {
+        self.log(format!("shuffle {}", shuffle));
+        self.state.lock().unwrap().shuffle = shuffle;
+        Ok(())
+    }
+
+    async fn set_repeat(&self, repeat: RepeatState) -> Result<()> {
+        self.log(format!("repeat {}", repeat.as_str()));
+        self.state.lock().unwrap().repeat = repeat;
+        Ok(())
+    }
+
+    async fn seek(&self, position_ms: u32) -> Result<()> {
+        self.log(format!("seek {}", position_ms));
+        self.state.lock().unwrap().progress_ms = min(position_ms, TRACK_DURATION_MS);
+        Ok(())
+    }
+
+    async fn play_context(&mut self, context_uri: String, position: Option<u32>) -> Result<()> {
+        let tracks = self.playlists.iter()
+            .find(|p| p.uri() == context_uri)
+            .map(|p| p.tracks.clone())
+            .ok_or_else(|| anyhow!("Can't play {}", context_uri))?;
+        let position = position.unwrap_or(0);
+        self.log(format!("play {} at {}", context_uri, position));
+        {
+            let mut state = self.state.lock().unwrap();
+            state.queue = tracks;
+            state.position = position as usize;
+            state.progress_ms = 0;
+            self.start_playing(&mut state)?;
+        }
+        self.playing = true;
+        Ok(())
+    }
+
+    async fn play_from_uri(&mut self, uri: String) -> Result<()> {
+        let track = self.playlists.iter()
+            .flat_map(|p| p.tracks.iter())
+            .find(|t| t.uri() == uri)
+            .cloned()
+            .ok_or_else(|| anyhow!("Can't play {}", uri))?;
+        self.log(format!("play {}", uri));
+        {
+            let mut state = self.state.lock().unwrap();
+            state.queue = vec![track];
+            state.position = 0;
+            state.progress_ms = 0;
+            self.start_playing(&mut state)?;
+        }
+        self.playing = true;
+        Ok(())
+    }
+}
+
+// The fake builds rspotify's types from the same json Spotify sends, so the real conversions get used
+fn from_json<T: DeserializeOwned>(value: Value) -> T {
+    serde_json::from_value(value).unwrap()
+}
+
+fn page_of<T>(items: Vec<T>, offset: usize, total: usize) -> Page<T> {
+    let end = offset + items.len();
+    Page {
+        href: String::new(),
+        limit: PAGE_SIZE,
+        next: Some(format!("fake://next?offset={}", end)).filter(|_| end < total),
+        offset: offset as u32,
+        previous: None,
+        total: total as u32,
+        items,
+    }
+}
+
+fn user_json() -> Value {
+    json!({
+        "display_name": "Fake User",
+        "external_urls": {},
+        "href": "",
+        "id": OWNER_ID,
+        "type": "user",
+        "uri": format!("spotify:user:{}", OWNER_ID),
+    })
+}
+
+fn summary_json(p: &FakePlaylist) -> Value {
+    json!({
+        "collaborative": false,
+        "external_urls": {},
+        "href": "",
+        "id": p.id,
+        "images": [],
+        "name": p.name,
+        "owner": user_json(),
+        "public": true,
+        "snapshot_id": p.snapshot_id(),
+        "tracks": { "total": p.tracks.len() },
+        "type": "playlist",
+        "uri": p.uri(),
+    })
+}
+
+fn track_json(t: &FakeTrack) -> Value {
+    json!({
+        "album": {
+            "album_type": "album",
+            "artists": [],
+            "external_urls": {},
+            "href": null,
+            "id": null,
+            "images": [],
+            "name": t.album,
+            "type": "album",
+            "uri": null,
+        },
+        "artists": [{
+            "external_urls": {},
+            "href": null,
+            "id": null,
+            "name": t.artist,
+            "type": "artist",
+            "uri": null,
+        }],
+        "disc_number": 1,
+        "duration_ms": TRACK_DURATION_MS,
+        "explicit": false,
+        "external_ids": {},
+        "external_urls": {},
+        "href": null,
+        "id": t.id,
+        "is_local": false,
+        "name": t.name,
+        "popularity": 0,
+        "preview_url": null,
+        "track_number": 1,
+        "type": "track",
+        "uri": t.uri(),
+    })
+}
+
+fn playlist_track_json(t: &FakeTrack) -> Value {
+    json!({
+        "added_at": "2020-01-01T00:00:00Z",
+        "added_by": null,
+        "is_local": false,
+        "track": track_json(t),
+    })
+}
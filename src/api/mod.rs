@@ -1,12 +1,32 @@
+mod album;
+mod artist;
+mod auth;
+mod backend;
 mod cursor;
+mod device;
+#[cfg(test)]
+mod fake_backend;
+mod offline_backend;
 mod paged;
+mod playback;
 mod playlist;
 mod playlist_summary;
+mod search;
 mod spotify_api;
 mod track;
-pub use cursor::Cursor;
+pub use album::Album;
+pub use artist::Artist;
+pub use auth::{login, Auth};
+pub use backend::{choose_device_id, MusicBackend, TransientError};
+pub use cursor::{contains_ignore_case, Cursor, Searchable};
+pub use device::Device;
+#[cfg(test)]
+pub use fake_backend::FakeBackend;
+pub use offline_backend::OfflineBackend;
 pub use paged::{NextPage, Paged, PageId};
+pub use playback::{format_time, next_repeat_state, PlaybackState};
 pub use playlist::Playlist;
 pub use playlist_summary::PlaylistSummary;
-pub use spotify_api::SpotifyApi;
+pub use search::{SearchKind, SearchPage, SearchResults};
+pub use spotify_api::{SpotifyApi, PAGE_SIZE};
 pub use track::{PlaylistTrack, Track};
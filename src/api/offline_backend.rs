@@ -0,0 +1,160 @@
+// A backend for when Spotify can't be reached. It only knows the playlists saved in the
+// library cache from earlier runs, so browsing and searching them still works, but there's
+// nothing to play on
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use rspotify::senum::RepeatState;
+
+use crate::api::{
+    Device,
+    MusicBackend,
+    Paged,
+    PageId,
+    PlaybackState,
+    Playlist,
+    PlaylistSummary,
+    PlaylistTrack,
+    SearchKind,
+    SearchPage,
+    Searchable,
+    Track,
+};
+use crate::cache;
+
+const CANT_PLAY: &str = "Spotify can't be reached, so nothing can be played while offline";
+const NOT_SAVED: &str = "isn't saved for offline use";
+
+pub struct OfflineBackend {
+    dir: PathBuf,
+}
+
+impl OfflineBackend {
+    // `dir` is the library cache dir
+    pub fn new(dir: PathBuf) -> OfflineBackend {
+        OfflineBackend { dir }
+    }
+}
+
+#[async_trait]
+impl MusicBackend for OfflineBackend {
+    // All the saved pages come back as the first one
+    async fn get_playlists(&self, page: u32) -> Result<Paged<PlaylistSummary>> {
+        if page > 0 {
+            bail!("The rest of the playlists {}", NOT_SAVED);
+        }
+        cache::load_summaries(&self.dir)
+            .ok_or_else(|| anyhow!("Nothing is saved for offline use yet, start spotivi online first"))
+    }
+
+    async fn get_playlist(&self, id: &str) -> Result<Playlist> {
+        cache::load_any_playlist(&self.dir, id)
+            .ok_or_else(|| anyhow!("That playlist {}", NOT_SAVED))
+    }
+
+    // Only the tracks that had been loaded when the playlist was saved are there
+    async fn get_playlist_tracks(&self, _owner_id: &str, _id: &str, _offset: u32) -> Result<Paged<PlaylistTrack>> {
+        bail!("The rest of the playlist {}", NOT_SAVED)
+    }
+
+    // Everything that matches comes back in the first page
+    async fn search(&self, query: &str, kind: SearchKind, offset: u32) -> Result<SearchPage> {
+        let page_id = PageId::Search { query: query.to_owned(), kind };
+        let pattern = query.to_lowercase();
+        let first = offset == 0;
+        Ok(match kind {
+            SearchKind::Tracks => {
+                let mut seen = HashSet::new();
+                let tracks = cache::load_all_playlists(&self.dir).iter()
+                    .filter(|_| first)
+                    .flat_map(|p| p.items().iter())
+                    .filter_map(|t| t.track.as_ref())
+                    .filter(|t| t.matches(&pattern))
+                    .filter(|t| seen.insert(t.full_track.uri.clone()))
+                    .map(|t| Track::new(t.full_track.clone()))
+                    .collect();
+                SearchPage::Tracks(Paged::from_items(tracks, page_id))
+            }
+            SearchKind::Playlists => {
+                let playlists = cache::load_summaries(&self.dir)
+                    .filter(|_| first)
+                    .map(|p| p.into_items())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|p| p.matches(&pattern))
+                    .collect();
+                SearchPage::Playlists(Paged::from_items(playlists, page_id))
+            }
+            SearchKind::Albums => SearchPage::Albums(Paged::from_items(Vec::new(), page_id)),
+            SearchKind::Artists => SearchPage::Artists(Paged::from_items(Vec::new(), page_id)),
+        })
+    }
+
+    // Polled all the time, so these just find nothing rather than fail
+    async fn get_devices(&self) -> Result<Vec<Device>> {
+        Ok(Vec::new())
+    }
+
+    async fn playback_status(&self) -> Result<Option<PlaybackState>> {
+        Ok(None)
+    }
+
+    fn device_id(&self) -> Option<&str> {
+        None
+    }
+
+    fn choose_device(&mut self, _devices: &[Device], _saved_id: Option<String>) {}
+
+    fn set_playing(&mut self, _playing: bool) {}
+
+    async fn transfer_playback(&mut self, _id: String, _play: bool) -> Result<()> {
+        bail!(CANT_PLAY)
+    }
+
+    async fn toggle_playback(&mut self) -> Result<()> {
+        bail!(CANT_PLAY)
+    }
+
+    async fn resume_playback(&mut self) -> Result<()> {
+        bail!(CANT_PLAY)
+    }
+
+    async fn pause_playback(&mut self) -> Result<()> {
+        bail!(CANT_PLAY)
+    }
+
+    async fn next_track(&self) -> Result<()> {
+        bail!(CANT_PLAY)
+    }
+
+    async fn previous_track(&self) -> Result<()> {
+        bail!(CANT_PLAY)
+    }
+
+    async fn set_volume(&self, _volume_percent: u8) -> Result<()> {
+        bail!(CANT_PLAY)
+    }
+
+    async fn set_shuffle(&self, _shuffle: bool) -> Result<()> {
+        bail!(CANT_PLAY)
+    }
+
+    async fn set_repeat(&self, _repeat: RepeatState) -> Result<()> {
+        bail!(CANT_PLAY)
+    }
+
+    async fn seek(&self, _position_ms: u32) -> Result<()> {
+        bail!(CANT_PLAY)
+    }
+
+    async fn play_context(&mut self, _context_uri: String, _position: Option<u32>) -> Result<()> {
+        bail!(CANT_PLAY)
+    }
+
+    async fn play_from_uri(&mut self, _uri: String) -> Result<()> {
+        bail!(CANT_PLAY)
+    }
+}
@@ -1,15 +1,19 @@
 use rspotify::model::page::Page;
+use serde::{Deserialize, Serialize};
 
-use crate::send_request;
+use crate::api::SearchKind;
 use crate::app::NetworkRequest;
+use crate::store::Store;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum PageId {
     Playlists,
     Playlist(String),
+    Devices,
+    Search { query: String, kind: SearchKind },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct NextPage {
     pub index: u32,
     pub uri: String,
@@ -23,7 +27,7 @@ impl NextPage {
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Paged<T> {
     items: Vec<T>,
     next_page: Option<NextPage>,
@@ -49,6 +53,10 @@ impl<T> Paged<T> {
         &self.items
     }
 
+    pub fn into_items(self) -> Vec<T> {
+        self.items
+    }
+
     pub fn next_page(&self) -> Option<&NextPage> {
         self.next_page.as_ref()
     }
@@ -57,14 +65,23 @@ impl<T> Paged<T> {
         self.items.len()
     }
 
-    pub fn load_next(&self) {
+    pub fn load_next(&self, store: &Store) {
         if let Some(_) = self.next_page {
-            send_request(NetworkRequest::LoadNextPage(self.page_id.clone()));
+            store.send_request(NetworkRequest::LoadNextPage(self.page_id.clone()));
         }
     }
 }
 
 impl<T> Paged<T> {
+    // For things Spotify returns all at once, so there's never a next page
+    pub fn from_items(items: Vec<T>, page_id: PageId) -> Paged<T> {
+        Paged {
+            items,
+            next_page: None,
+            page_id,
+        }
+    }
+
     pub fn from<U: Into<T>>(page: Page<U>, page_id: PageId) -> Paged<T> {
         Paged {
             items: page.items.into_iter().map(Into::into).collect(),
@@ -0,0 +1,172 @@
+use std::time::Instant;
+
+use itertools::Itertools;
+use rspotify::model::PlayingItem;
+use rspotify::model::context::CurrentlyPlaybackContext;
+use rspotify::senum::RepeatState;
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug)]
+pub struct PlaybackState {
+    title: Option<String>,
+    artists: Option<String>,
+    device_name: String,
+    volume_percent: u32,
+    is_playing: bool,
+    shuffle: bool,
+    repeat: RepeatState,
+    progress_ms: u32,
+    duration_ms: u32,
+    fetched_at: Instant,
+}
+
+// How the playback state looks to other programs, from `spotivi status --json` and the control socket
+#[derive(Serialize)]
+struct PlaybackJson<'a> {
+    playing: bool,
+    title: Option<&'a str>,
+    artists: Option<&'a str>,
+    device: &'a str,
+    volume_percent: u32,
+    shuffle: bool,
+    repeat: &'static str,
+    progress_ms: u32,
+    duration_ms: u32,
+}
+
+impl PlaybackState {
+    pub fn to_json(&self) -> Value {
+        let json = PlaybackJson {
+            playing: self.is_playing,
+            title: self.title(),
+            artists: self.artists(),
+            device: &self.device_name,
+            volume_percent: self.volume_percent,
+            shuffle: self.shuffle,
+            repeat: match self.repeat {
+                RepeatState::Off => "off",
+                RepeatState::Context => "context",
+                RepeatState::Track => "track",
+            },
+            progress_ms: self.progress_ms(),
+            duration_ms: self.duration_ms,
+        };
+        serde_json::to_value(json).unwrap()
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_ref().map(|s| &s[..])
+    }
+
+    pub fn artists(&self) -> Option<&str> {
+        self.artists.as_ref().map(|s| &s[..])
+    }
+
+    pub fn device_name(&self) -> &str {
+        &self.device_name
+    }
+
+    pub fn volume_percent(&self) -> u32 {
+        self.volume_percent
+    }
+
+    pub fn set_volume_percent(&mut self, volume_percent: u32) {
+        self.volume_percent = volume_percent;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.is_playing
+    }
+
+    pub fn shuffle(&self) -> bool {
+        self.shuffle
+    }
+
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        self.shuffle = shuffle;
+    }
+
+    pub fn repeat(&self) -> RepeatState {
+        self.repeat
+    }
+
+    pub fn set_repeat(&mut self, repeat: RepeatState) {
+        self.repeat = repeat;
+    }
+
+    pub fn duration_ms(&self) -> u32 {
+        self.duration_ms
+    }
+
+    // The api is only polled every few seconds, so estimate the progress since then
+    pub fn progress_ms(&self) -> u32 {
+        if self.is_playing {
+            let elapsed = self.fetched_at.elapsed().as_millis() as u32;
+            std::cmp::min(self.progress_ms.saturating_add(elapsed), self.duration_ms)
+        } else {
+            self.progress_ms
+        }
+    }
+
+    pub fn set_progress_ms(&mut self, progress_ms: u32) {
+        self.progress_ms = progress_ms;
+        self.fetched_at = Instant::now();
+    }
+
+    // Where seeking by `offset_ms` from the current position would land, kept within the track
+    pub fn seek_position(&self, offset_ms: i64) -> u32 {
+        let position = self.progress_ms() as i64 + offset_ms;
+        std::cmp::max(0, std::cmp::min(position, self.duration_ms as i64)) as u32
+    }
+
+    pub fn percent_position(&self, percent: u8) -> u32 {
+        (self.duration_ms as u64 * std::cmp::min(percent, 100) as u64 / 100) as u32
+    }
+}
+
+impl From<CurrentlyPlaybackContext> for PlaybackState {
+    fn from(ctx: CurrentlyPlaybackContext) -> PlaybackState {
+        let (title, artists, duration_ms) = match ctx.item {
+            Some(PlayingItem::Track(t)) => (
+                Some(t.name),
+                Some(t.artists.iter().map(|a| &a.name).join(", ")),
+                t.duration_ms,
+            ),
+            Some(PlayingItem::Episode(e)) => (
+                Some(e.name),
+                Some(e.show.name),
+                e.duration_ms,
+            ),
+            None => (None, None, 0),
+        };
+
+        PlaybackState {
+            title,
+            artists,
+            device_name: ctx.device.name,
+            volume_percent: ctx.device.volume_percent,
+            is_playing: ctx.is_playing,
+            shuffle: ctx.shuffle_state,
+            repeat: ctx.repeat_state,
+            progress_ms: ctx.progress_ms.unwrap_or(0),
+            duration_ms,
+            fetched_at: Instant::now(),
+        }
+    }
+}
+
+// Off -> context -> track -> off, the same order the official clients use
+pub fn next_repeat_state(repeat: RepeatState) -> RepeatState {
+    match repeat {
+        RepeatState::Off => RepeatState::Context,
+        RepeatState::Context => RepeatState::Track,
+        RepeatState::Track => RepeatState::Off,
+    }
+}
+
+// Like 3:07, the way the status bar and `spotivi status` show where a track is
+pub fn format_time(ms: u32) -> String {
+    let secs = ms / 1000;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
@@ -1,11 +1,15 @@
 use rspotify::model::playlist::FullPlaylist;
+use serde::{Deserialize, Serialize};
 
 use crate::api::{Paged, PageId, PlaylistTrack};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Playlist {
     id: String,
+    snapshot_id: String,
+    uri: String,
     name: String,
+    owner_id: String,
     tracks: Paged<PlaylistTrack>,
 }
 
@@ -14,10 +18,22 @@ impl Playlist {
         &self.id
     }
 
+    pub fn snapshot_id(&self) -> &str {
+        &self.snapshot_id
+    }
+
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    pub fn owner_id(&self) -> &str {
+        &self.owner_id
+    }
+
     pub fn items(&self) -> &Vec<PlaylistTrack> {
         self.tracks.items()
     }
@@ -25,13 +41,24 @@ impl Playlist {
     pub fn tracks(&self) -> &Paged<PlaylistTrack> {
         &self.tracks
     }
+
+    // The same page can be requested again before the first one arrives, so only add
+    // tracks that start right where the loaded ones end
+    pub fn add_tracks(&mut self, offset: usize, tracks: Paged<PlaylistTrack>) {
+        if offset == self.tracks.len() {
+            self.tracks.add_page(tracks);
+        }
+    }
 }
 
 impl From<FullPlaylist> for Playlist {
     fn from(p: FullPlaylist) -> Playlist {
         Playlist {
             id: p.id.clone(),
+            snapshot_id: p.snapshot_id,
+            uri: p.uri,
             name: p.name,
+            owner_id: p.owner.id,
             tracks: Paged::from(p.tracks, PageId::Playlist(p.id.clone())),
         }
     }
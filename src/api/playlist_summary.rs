@@ -1,12 +1,15 @@
-use anyhow::Result;
 use rspotify::model::playlist::SimplifiedPlaylist;
+use serde::{Deserialize, Serialize};
 
+use crate::api::{contains_ignore_case, Searchable};
 use crate::views::Popup;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PlaylistSummary {
     name: String,
     id: String,
+    // Changes whenever the playlist does
+    snapshot_id: String,
     collaborative: bool,
     owner_name: Option<String>,
     public: Option<bool>,
@@ -21,6 +24,10 @@ impl PlaylistSummary {
         &self.id
     }
 
+    pub fn snapshot_id(&self) -> &str {
+        &self.snapshot_id
+    }
+
     pub fn collaborative(&self) -> bool {
         self.collaborative
     }
@@ -33,7 +40,7 @@ impl PlaylistSummary {
         self.public
     }
 
-    pub fn info_popup(&self) -> Result<Popup> {
+    pub fn info_popup(&self) -> Popup {
         let mut lines = vec![
             format!("Name: {}", self.name),
             format!("Owner: {}", self.owner_name().unwrap_or("<unknown>")),
@@ -49,11 +56,19 @@ impl PlaylistSummary {
     }
 }
 
+impl Searchable for PlaylistSummary {
+    fn matches(&self, pattern: &str) -> bool {
+        contains_ignore_case(&self.name, pattern)
+            || self.owner_name().map(|o| contains_ignore_case(o, pattern)).unwrap_or(false)
+    }
+}
+
 impl From<SimplifiedPlaylist> for PlaylistSummary {
     fn from(p: SimplifiedPlaylist) -> PlaylistSummary {
         PlaylistSummary {
             name: p.name,
             id: p.id,
+            snapshot_id: p.snapshot_id,
             collaborative: p.collaborative,
             owner_name: p.owner.display_name,
             public: p.public,
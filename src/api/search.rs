@@ -0,0 +1,118 @@
+use rspotify::senum::SearchType;
+use serde::{Deserialize, Serialize};
+
+use crate::api::{Album, Artist, Paged, PageId, PlaylistSummary, Track};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SearchKind {
+    Tracks,
+    Albums,
+    Artists,
+    Playlists,
+}
+
+impl SearchKind {
+    pub const ALL: [SearchKind; 4] = [
+        SearchKind::Tracks,
+        SearchKind::Albums,
+        SearchKind::Artists,
+        SearchKind::Playlists,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SearchKind::Tracks => "Tracks",
+            SearchKind::Albums => "Albums",
+            SearchKind::Artists => "Artists",
+            SearchKind::Playlists => "Playlists",
+        }
+    }
+
+    pub fn search_type(self) -> SearchType {
+        match self {
+            SearchKind::Tracks => SearchType::Track,
+            SearchKind::Albums => SearchType::Album,
+            SearchKind::Artists => SearchType::Artist,
+            SearchKind::Playlists => SearchType::Playlist,
+        }
+    }
+}
+
+// One page of results for a single kind
+#[derive(Debug)]
+pub enum SearchPage {
+    Tracks(Paged<Track>),
+    Albums(Paged<Album>),
+    Artists(Paged<Artist>),
+    Playlists(Paged<PlaylistSummary>),
+}
+
+impl SearchPage {
+    pub fn kind(&self) -> SearchKind {
+        match self {
+            SearchPage::Tracks(_) => SearchKind::Tracks,
+            SearchPage::Albums(_) => SearchKind::Albums,
+            SearchPage::Artists(_) => SearchKind::Artists,
+            SearchPage::Playlists(_) => SearchKind::Playlists,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SearchResults {
+    query: String,
+    pub tracks: Paged<Track>,
+    pub albums: Paged<Album>,
+    pub artists: Paged<Artist>,
+    pub playlists: Paged<PlaylistSummary>,
+}
+
+impl SearchResults {
+    pub fn new(query: String) -> SearchResults {
+        let page_id = |kind| PageId::Search { query: query.clone(), kind };
+        SearchResults {
+            tracks: Paged::new(page_id(SearchKind::Tracks)),
+            albums: Paged::new(page_id(SearchKind::Albums)),
+            artists: Paged::new(page_id(SearchKind::Artists)),
+            playlists: Paged::new(page_id(SearchKind::Playlists)),
+            query,
+        }
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn len(&self, kind: SearchKind) -> usize {
+        match kind {
+            SearchKind::Tracks => self.tracks.len(),
+            SearchKind::Albums => self.albums.len(),
+            SearchKind::Artists => self.artists.len(),
+            SearchKind::Playlists => self.playlists.len(),
+        }
+    }
+
+    pub fn has_next_page(&self, kind: SearchKind) -> bool {
+        match kind {
+            SearchKind::Tracks => self.tracks.next_page().is_some(),
+            SearchKind::Albums => self.albums.next_page().is_some(),
+            SearchKind::Artists => self.artists.next_page().is_some(),
+            SearchKind::Playlists => self.playlists.next_page().is_some(),
+        }
+    }
+
+    // Results can arrive after the query has changed, or twice for the same offset,
+    // so only add pages that continue the current results
+    pub fn add_page(&mut self, query: &str, offset: usize, page: SearchPage) {
+        if query != self.query {
+            return;
+        }
+        match page {
+            SearchPage::Tracks(p) if offset == self.tracks.len() => self.tracks.add_page(p),
+            SearchPage::Albums(p) if offset == self.albums.len() => self.albums.add_page(p),
+            SearchPage::Artists(p) if offset == self.artists.len() => self.artists.add_page(p),
+            SearchPage::Playlists(p) if offset == self.playlists.len() => self.playlists.add_page(p),
+            _ => {}
+        }
+    }
+}
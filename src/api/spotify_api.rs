@@ -1,62 +1,126 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
-use rspotify::client::Spotify;
-use rspotify::model::device::Device;
-use rspotify::model::context::CurrentlyPlaybackContext;
-use rspotify::oauth2::SpotifyClientCredentials;
-use rspotify::oauth2::SpotifyOAuth;
+use async_trait::async_trait;
+use rspotify::client::ApiError;
+use rspotify::model::offset;
+use rspotify::model::search::SearchResult;
+use rspotify::senum::RepeatState;
 
 use crate::config::Config;
-use crate::api::{Paged, PageId, Playlist, PlaylistSummary};
+use crate::api::{
+    choose_device_id,
+    Auth,
+    Device,
+    MusicBackend,
+    Paged,
+    PageId,
+    PlaybackState,
+    Playlist,
+    PlaylistSummary,
+    PlaylistTrack,
+    SearchKind,
+    SearchPage,
+    TransientError,
+};
 
 // Spotify's api doesn't allow this to go higher than 50...
 pub const PAGE_SIZE: u32 = 50;
 
 pub struct SpotifyApi {
-    client: Spotify,
+    auth: Auth,
     device_id: Option<String>,
     playing: bool,
 }
 
 impl SpotifyApi {
-    pub async fn new(config: Arc<Config>) -> Option<SpotifyApi> {
-        Some(SpotifyApi {
-            client: make_spotify_client(config).await?,
+    pub async fn new(config: Arc<Config>) -> Result<SpotifyApi> {
+        Ok(SpotifyApi {
+            auth: Auth::load(config).await?,
             device_id: None,
             playing: false,
         })
     }
 
-    pub async fn get_playlists(&self, page_num: u32) -> Result<Paged<PlaylistSummary>> {
-        self.client
+    // For staying logged in through long sessions
+    pub fn keep_token_fresh(&self) {
+        self.auth.keep_fresh();
+    }
+}
+
+#[async_trait]
+impl MusicBackend for SpotifyApi {
+    async fn get_playlists(&self, page_num: u32) -> Result<Paged<PlaylistSummary>> {
+        self.auth.client().await?
             .current_user_playlists(PAGE_SIZE, PAGE_SIZE * page_num)
             .await
-            .map_err(|e| anyhow!(e))
+            .map_err(api_error)
             .map(|p| Paged::from(p, PageId::Playlists))
     }
 
-    pub async fn get_playlist(&self, id: &str) -> Result<Playlist> {
-        self.client
+    async fn get_playlist(&self, id: &str) -> Result<Playlist> {
+        self.auth.client().await?
             .playlist(id, None, None)
             .await
-            .map_err(|e| anyhow!(e))
+            .map_err(api_error)
             .map(Into::into)
     }
 
-    pub async fn get_devices(&self) -> Result<Vec<Device>> {
-        self.client
+    async fn get_playlist_tracks(&self, owner_id: &str, id: &str, offset: u32) -> Result<Paged<PlaylistTrack>> {
+        self.auth.client().await?
+            .user_playlist_tracks(owner_id, id, None, PAGE_SIZE, offset, None)
+            .await
+            .map_err(api_error)
+            .map(|p| Paged::from(p, PageId::Playlist(id.to_owned())))
+    }
+
+    async fn search(&self, query: &str, kind: SearchKind, offset: u32) -> Result<SearchPage> {
+        let result = self.auth.client().await?
+            .search(query, kind.search_type(), PAGE_SIZE, offset, None, None)
+            .await
+            .map_err(api_error)?;
+
+        let page_id = PageId::Search { query: query.to_owned(), kind };
+        Ok(match result {
+            SearchResult::Tracks(p) => SearchPage::Tracks(Paged::from(p, page_id)),
+            SearchResult::Albums(p) => SearchPage::Albums(Paged::from(p, page_id)),
+            SearchResult::Artists(p) => SearchPage::Artists(Paged::from(p, page_id)),
+            SearchResult::Playlists(p) => SearchPage::Playlists(Paged::from(p, page_id)),
+            _ => bail!("unexpected search result for {}", kind.name()),
+        })
+    }
+
+    async fn get_devices(&self) -> Result<Vec<Device>> {
+        self.auth.client().await?
             .device()
             .await
-            .map_err(|e| anyhow!(e))
-            .map(|p| p.devices)
+            .map_err(api_error)
+            .map(|p| p.devices.into_iter().map(Into::into).collect())
+    }
+
+    fn device_id(&self) -> Option<&str> {
+        self.device_id.as_ref().map(|s| &s[..])
+    }
+
+    fn choose_device(&mut self, devices: &[Device], saved_id: Option<String>) {
+        if self.device_id.is_none() {
+            self.device_id = choose_device_id(devices, saved_id);
+        }
     }
 
-    pub fn set_device_id(&mut self, id: Option<String>) {
-        self.device_id = id;
+    async fn transfer_playback(&mut self, id: String, play: bool) -> Result<()> {
+        self.auth.client().await?
+            .transfer_playback(&id, play)
+            .await
+            .map_err(api_error)?;
+
+        self.device_id = Some(id);
+        self.playing = play;
+        Ok(())
     }
 
-    pub async fn toggle_playback(&mut self) -> Result<()> {
+    async fn toggle_playback(&mut self) -> Result<()> {
         if self.playing {
             self.pause_playback().await
         } else {
@@ -64,8 +128,8 @@ impl SpotifyApi {
         }
     }
 
-    pub async fn resume_playback(&mut self) -> Result<()> {
-        self.client
+    async fn resume_playback(&mut self) -> Result<()> {
+        self.auth.client().await?
             .start_playback(
                 self.device_id.clone(),
                 None,
@@ -74,35 +138,94 @@ impl SpotifyApi {
                 None,
             )
             .await
-            .map_err(|e| anyhow!(e))?;
+            .map_err(api_error)?;
 
         self.playing = true;
         Ok(())
     }
 
-    pub async fn pause_playback(&mut self) -> Result<()> {
-        self.client
+    async fn pause_playback(&mut self) -> Result<()> {
+        self.auth.client().await?
             .pause_playback(self.device_id.clone())
             .await
-            .map_err(|e| anyhow!(e))?;
+            .map_err(api_error)?;
 
         self.playing = false;
         Ok(())
     }
 
-    pub async fn playback_status(&self) -> Result<Option<CurrentlyPlaybackContext>> {
-        self.client
+    async fn playback_status(&self) -> Result<Option<PlaybackState>> {
+        self.auth.client().await?
             .current_playback(None, None)
             .await
-            .map_err(|e| anyhow!(e))
+            .map_err(api_error)
+            .map(|p| p.map(PlaybackState::from))
     }
 
-    pub fn set_playing(&mut self, playing: bool) {
+    fn set_playing(&mut self, playing: bool) {
         self.playing = playing;
     }
 
-    pub async fn play_from_uri(&mut self, uri: String) -> Result<()> {
-        self.client
+    async fn next_track(&self) -> Result<()> {
+        self.auth.client().await?
+            .next_track(self.device_id.clone())
+            .await
+            .map_err(api_error)
+    }
+
+    async fn previous_track(&self) -> Result<()> {
+        self.auth.client().await?
+            .previous_track(self.device_id.clone())
+            .await
+            .map_err(api_error)
+    }
+
+    async fn set_volume(&self, volume_percent: u8) -> Result<()> {
+        self.auth.client().await?
+            .volume(volume_percent, self.device_id.clone())
+            .await
+            .map_err(api_error)
+    }
+
+    async fn set_shuffle(&self, shuffle: bool) -> Result<()> {
+        self.auth.client().await?
+            .shuffle(shuffle, self.device_id.clone())
+            .await
+            .map_err(api_error)
+    }
+
+    async fn set_repeat(&self, repeat: RepeatState) -> Result<()> {
+        self.auth.client().await?
+            .repeat(repeat, self.device_id.clone())
+            .await
+            .map_err(api_error)
+    }
+
+    async fn seek(&self, position_ms: u32) -> Result<()> {
+        self.auth.client().await?
+            .seek_track(position_ms, self.device_id.clone())
+            .await
+            .map_err(api_error)
+    }
+
+    async fn play_context(&mut self, context_uri: String, position: Option<u32>) -> Result<()> {
+        self.auth.client().await?
+            .start_playback(
+                self.device_id.clone(),
+                Some(context_uri),
+                None,
+                position.and_then(offset::for_position),
+                None,
+            )
+            .await
+            .map_err(api_error)?;
+
+        self.playing = true;
+        Ok(())
+    }
+
+    async fn play_from_uri(&mut self, uri: String) -> Result<()> {
+        self.auth.client().await?
             .start_playback(
                 self.device_id.clone(),
                 None,
@@ -111,26 +234,40 @@ impl SpotifyApi {
                 None,
             )
             .await
-            .map_err(|e| anyhow!(e))?;
+            .map_err(api_error)?;
 
         self.playing = true;
         Ok(())
     }
 }
 
-async fn make_spotify_client(config: Arc<Config>) -> Option<Spotify> {
-    let mut oauth = SpotifyOAuth::default()
-        .scope("streaming user-modify-playback-state user-read-playback-state playlist-read-private")
-        .client_id(&config.api_client_id)
-        .client_secret(&config.api_client_secret)
-        .redirect_uri(&config.redirect_uri)
-        .cache_path(config.cache_path.clone())
-        .build();
-    let token = rspotify::util::get_token(&mut oauth).await?;
-    let credentials = SpotifyClientCredentials::default()
-        .token_info(token)
-        .build();
-    Some(Spotify::default()
-        .client_credentials_manager(credentials)
-        .build())
+// Keeps the failures worth retrying recognizable once they're anyhow errors
+fn api_error(e: failure::Error) -> anyhow::Error {
+    match e.downcast_ref::<ApiError>() {
+        Some(ApiError::RateLimited(seconds)) => {
+            return TransientError::RateLimited(seconds.map(|s| Duration::from_secs(s as u64))).into();
+        }
+        Some(ApiError::Other(status)) if *status >= 500 => return TransientError::Server(*status).into(),
+        _ => {}
+    }
+    let unreachable = e.downcast_ref::<reqwest::Error>()
+        .filter(|e| e.is_timeout() || never_sent(e))
+        .map(|e| e.to_string());
+    match unreachable {
+        Some(message) => TransientError::Network(message).into(),
+        None => anyhow!(e),
+    }
+}
+
+// Whether `e` is from not being able to connect at all, like when the network is down. This
+// reqwest doesn't have is_connect, but those failures are the only request errors from an io error
+fn never_sent(e: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(e);
+    while let Some(inner) = source {
+        if inner.is::<std::io::Error>() {
+            return e.is_request();
+        }
+        source = inner.source();
+    }
+    false
 }
@@ -1,13 +1,13 @@
-use anyhow::Result;
-
 use itertools::Itertools;
 
 use rspotify::model::playlist::PlaylistTrack as PT;
 use rspotify::model::track::FullTrack;
+use serde::{Deserialize, Serialize};
 
+use crate::api::{contains_ignore_case, Searchable};
 use crate::views::Popup;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Track {
     pub full_track: FullTrack,
 }
@@ -17,16 +17,33 @@ impl Track {
         Track { full_track }
     }
 
-    pub fn info_popup(&self) -> Result<Popup> {
-        let artist = self.full_track.artists.iter().map(|a| &a.name).join(", ");
+    pub fn artists(&self) -> String {
+        self.full_track.artists.iter().map(|a| &a.name).join(", ")
+    }
+
+    pub fn info_popup(&self) -> Popup {
         Popup::new(vec![
            format!("Title: {}", self.full_track.name),
-           format!("Artist: {}", artist),
+           format!("Artist: {}", self.artists()),
         ])
     }
 }
 
-#[derive(Debug)]
+impl Searchable for Track {
+    fn matches(&self, pattern: &str) -> bool {
+        contains_ignore_case(&self.full_track.name, pattern)
+            || contains_ignore_case(&self.full_track.album.name, pattern)
+            || self.full_track.artists.iter().any(|a| contains_ignore_case(&a.name, pattern))
+    }
+}
+
+impl From<FullTrack> for Track {
+    fn from(t: FullTrack) -> Track {
+        Track::new(t)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PlaylistTrack {
     is_local: bool,
     pub track: Option<Track>,
@@ -46,3 +63,9 @@ impl From<PT> for PlaylistTrack {
         PlaylistTrack::new(pt)
     }
 }
+
+impl Searchable for PlaylistTrack {
+    fn matches(&self, pattern: &str) -> bool {
+        self.track.as_ref().map(|t| t.matches(pattern)).unwrap_or(false)
+    }
+}
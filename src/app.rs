@@ -1,48 +1,136 @@
 use std::io::{stdout, Write};
 use std::process::exit;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 
 use anyhow::Result;
+use tokio::time;
 use crossterm::{
+    event::{KeyCode, KeyEvent},
     execute,
     terminal as term,
     cursor,
     queue,
 };
 
-use crate::send_request;
 use crate::api::PageId;
+use crate::cache;
+use crate::command::{self, Command, CommandHistory, Completion, UriKind};
+use crate::control;
+use crate::store::{Events, Store, StoreEvent};
 use crate::views::{
-    BoundingBox,
+    prompt_bounds,
+    screen_bounds,
+    status_bar_bounds,
+    DevicesScreen,
+    PlaylistScreen,
     PlaylistsScreen,
+    Output,
     Screen,
+    SearchScreen,
     Popup,
+    Prompt,
+    StatusBar,
 };
-use crate::keybindings::KeyBinding;
+#[cfg(test)]
+use crate::views::ScreenId;
+use crate::keybindings::{KeyBinding, KeyParser, ParsedKey};
 use crate::config::Config;
 
+// Like vim's jump list, this is about how far back ctrl-o can go
+const MAX_HISTORY: usize = 100;
+
+// Volume keys only change what's shown until they haven't been pressed for this long,
+// so holding one down doesn't send a request for every step
+const VOLUME_DEBOUNCE: Duration = Duration::from_millis(300);
+
+// How many messages `:messages` keeps around
+const MAX_MESSAGES: usize = 100;
+
 pub struct App {
     screens: Vec<Box<dyn Screen + Send>>,
+    // Indexes into `screens`, in the order they were visited
+    history: Vec<usize>,
+    history_pos: usize,
     config: Arc<Config>,
+    store: Arc<Store>,
+    // Where everything is drawn, which is the terminal except in tests
+    output: Output,
+    keys: KeyParser,
     popup: Option<Popup>,
+    // Open while typing a pattern after `/`
+    find_prompt: Option<Prompt>,
+    // Open while typing a command after `:`
+    command_line: Option<Prompt>,
+    command_history: CommandHistory,
+    completion: Option<Completion>,
+    status_bar: StatusBar,
+    // The volume before muting, to go back to when unmuting
+    muted_volume: Option<u32>,
+    // Counts volume key presses, so a pending change can tell it has been replaced
+    volume_changes: Arc<AtomicUsize>,
+    // The last error or notice, shown on the bottom line until the next key
+    message: Option<String>,
+    // Every one so far, oldest first, for `:messages`
+    messages: Vec<String>,
+    // A playlist that's being loaded to be opened. Going anywhere else in the meantime, or it
+    // failing to load, means it won't be
+    opening: Option<String>,
+    // The control socket, if this instance is listening on one, to remove when quitting
+    socket: Option<control::Socket>,
 }
 
 impl App {
-    pub fn new(config: Arc<Config>) -> App {
+    pub fn new(config: Arc<Config>, store: Arc<Store>, output: Output) -> App {
+        let command_history = CommandHistory::load(config.command_history_path.clone(), config.command_history_size);
         let screens = vec![Box::new(PlaylistsScreen::new()) as Box<dyn Screen + Send>];
         App {
             screens,
+            history: vec![0],
+            history_pos: 0,
             config,
+            store,
+            output,
+            keys: KeyParser::new(),
             popup: None,
+            find_prompt: None,
+            command_line: None,
+            command_history,
+            completion: None,
+            status_bar: StatusBar::new(),
+            muted_volume: None,
+            volume_changes: Arc::new(AtomicUsize::new(0)),
+            message: None,
+            messages: Vec::new(),
+            opening: None,
+            socket: None,
+        }
+    }
+
+    // When loading a playlist that was to be opened fails
+    pub fn cancel_opening(&mut self, id: &str) {
+        if self.opening.as_deref() == Some(id) {
+            self.opening = None;
         }
     }
 
+    pub fn set_socket(&mut self, socket: control::Socket) {
+        self.socket = Some(socket);
+    }
+
     fn current_screen(&self) -> &dyn Screen {
-        self.screens.last().unwrap().as_ref()
+        self.screens[self.history[self.history_pos]].as_ref()
     }
 
     fn current_screen_mut(&mut self) -> &mut dyn Screen {
-        self.screens.last_mut().unwrap().as_mut()
+        self.screens[self.history[self.history_pos]].as_mut()
+    }
+
+    // Screens need the store for nearly everything, and it can't be borrowed through
+    // current_screen_mut
+    fn screen_and_store(&mut self) -> (&mut dyn Screen, &Store) {
+        (self.screens[self.history[self.history_pos]].as_mut(), &self.store)
     }
 
     pub fn start(&mut self) -> Result<()> {
@@ -57,12 +145,205 @@ impl App {
     }
 
     pub fn stop(&self) -> Result<()> {
-        term::disable_raw_mode()?;
-        execute!(
-            stdout(),
-            term::LeaveAlternateScreen,
-            cursor::Show,
-        )?;
+        if let Some(ref socket) = self.socket {
+            socket.remove();
+        }
+        restore_terminal()
+    }
+
+    pub fn handle_event(&mut self, e: KeyEvent) -> Result<()> {
+        if self.message.take().is_some() {
+            self.redraw_status_bar()?;
+        }
+
+        if self.find_prompt.is_some() {
+            self.handle_find_key(e)?;
+        } else if self.command_line.is_some() {
+            self.handle_command_line_key(e)?;
+        } else if self.popup.is_none() && self.current_screen().is_editing() {
+            let (screen, store) = self.screen_and_store();
+            if let Some(a) = screen.receive_text(store, e) {
+                self.handle_action(a)?;
+            }
+        } else if let ParsedKey::Binding(count, key) = self.keys.push(e, &self.config.keybindings) {
+            self.handle_key_with_count(key, count)?;
+        }
+        Ok(())
+    }
+
+    // The count goes to the screen's cursor, which is the only thing that uses one
+    fn handle_key_with_count(&mut self, key: KeyBinding, count: Option<usize>) -> Result<()> {
+        if let Some(cursor) = self.current_screen_mut().cursor_mut() {
+            cursor.set_count(count);
+        }
+        self.handle_key(key)?;
+        if let Some(cursor) = self.current_screen_mut().cursor_mut() {
+            cursor.set_count(None);
+        }
+        Ok(())
+    }
+
+    // The cursor follows the pattern as it's typed, like vim's incsearch
+    fn handle_find_key(&mut self, e: KeyEvent) -> Result<()> {
+        let prompt = match self.find_prompt {
+            Some(ref mut p) => p,
+            None => return Ok(()),
+        };
+
+        match e.code {
+            KeyCode::Esc => self.stop_find(false)?,
+            KeyCode::Backspace if prompt.input.is_empty() => self.stop_find(false)?,
+            // An empty pattern means the last one again, like vim
+            KeyCode::Enter if prompt.input.is_empty() => {
+                self.stop_find(false)?;
+                self.handle_key(KeyBinding::FindNext)?;
+            }
+            KeyCode::Enter => self.stop_find(true)?,
+            _ => {
+                if !prompt.input.handle_key(e) {
+                    return Ok(());
+                }
+                let pattern = prompt.input.text();
+                if let Some(cursor) = self.current_screen_mut().cursor_mut() {
+                    cursor.update_find(&pattern);
+                }
+                let bounds = screen_bounds(&self.output)?;
+                let (screen, store) = self.screen_and_store();
+                match screen.receive_input(store, KeyBinding::FindNext, bounds) {
+                    Some(a) => { self.handle_action(a)?; }
+                    None => self.redraw()?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn stop_find(&mut self, keep: bool) -> Result<()> {
+        self.find_prompt = None;
+        if let Some(cursor) = self.current_screen_mut().cursor_mut() {
+            cursor.end_find(keep);
+        }
+        self.redraw()
+    }
+
+    fn handle_command_line_key(&mut self, e: KeyEvent) -> Result<()> {
+        let prompt = match self.command_line {
+            Some(ref mut p) => p,
+            None => return Ok(()),
+        };
+
+        match e.code {
+            KeyCode::Esc => return self.close_command_line(),
+            KeyCode::Backspace if prompt.input.is_empty() => return self.close_command_line(),
+            KeyCode::Enter => {
+                let line = prompt.input.text();
+                self.close_command_line()?;
+                if line.trim().is_empty() {
+                    return Ok(());
+                }
+                // The command still runs when the history can't be written
+                let saved = self.command_history.push(line.trim());
+                match Command::parse(&line) {
+                    Ok(c) => self.run_command(c)?,
+                    Err(e) => self.display_popup(Popup::new(vec![e.to_string()]))?,
+                }
+                if let Err(e) = saved {
+                    self.handle_action(Action::Error(format!("Couldn't save history: {}", e)))?;
+                }
+                return Ok(());
+            }
+            KeyCode::Tab | KeyCode::BackTab => {
+                let text = prompt.input.text();
+                let store = &self.store;
+                let completion = self.completion.get_or_insert_with(|| Completion::new(&text, store));
+                prompt.input.set_text(completion.next(e.code == KeyCode::Tab));
+            }
+            KeyCode::Up => {
+                if let Some(line) = self.command_history.older(&prompt.input.text()) {
+                    prompt.input.set_text(line);
+                }
+                self.completion = None;
+            }
+            KeyCode::Down => {
+                if let Some(line) = self.command_history.newer() {
+                    prompt.input.set_text(line);
+                }
+                self.completion = None;
+            }
+            _ => {
+                if prompt.input.handle_key(e) {
+                    self.completion = None;
+                    self.command_history.reset();
+                }
+            }
+        }
+        self.redraw_status_bar()
+    }
+
+    fn close_command_line(&mut self) -> Result<()> {
+        self.command_line = None;
+        self.completion = None;
+        self.command_history.reset();
+        self.redraw_status_bar()
+    }
+
+    fn run_command(&mut self, command: Command) -> Result<()> {
+        match command {
+            Command::Play => self.store.send_request(NetworkRequest::Play),
+            Command::Pause => self.store.send_request(NetworkRequest::Pause),
+            Command::Next => self.store.send_request(NetworkRequest::NextTrack),
+            Command::Previous => self.store.send_request(NetworkRequest::PreviousTrack),
+            Command::Device(name) => {
+                let id = {
+                    let devices = self.store.devices();
+                    let name = name.to_lowercase();
+                    // An exact name wins over one that only starts with it
+                    devices.items().iter()
+                        .find(|d| d.name().to_lowercase() == name)
+                        .or_else(|| devices.items().iter().find(|d| d.name().to_lowercase().starts_with(&name)))
+                        .map(|d| d.id().to_owned())
+                };
+                match id {
+                    Some(id) => {
+                        let play = self.store.playback().as_ref().map(|p| p.is_playing()).unwrap_or(false);
+                        self.store.send_request(NetworkRequest::TransferPlayback(id, play));
+                    }
+                    None => self.display_popup(Popup::new(vec![format!("No device named {}", name)]))?,
+                }
+            }
+            Command::Volume(volume) => {
+                self.muted_volume = None;
+                self.change_volume(|_| volume as i32)?;
+            }
+            Command::Search(query) => {
+                self.add_screen(Box::new(SearchScreen::new()))?;
+                self.handle_action(Action::SubmitSearch(query))?;
+            }
+            Command::Open(UriKind::Playlist, id) => {
+                self.handle_action(Action::OpenPlaylist(id))?;
+            }
+            Command::Open(UriKind::Track, id) => {
+                self.store.send_request(NetworkRequest::PlayUri(command::uri(UriKind::Track, &id)));
+            }
+            Command::Open(kind, id) => {
+                self.store.send_request(NetworkRequest::PlayContext(command::uri(kind, &id), None));
+            }
+            // Only reads what's saved, so it works offline too
+            Command::Export(path) => {
+                match cache::export_playlists(&self.config.library_cache_dir, &path) {
+                    Ok(n) => {
+                        let plural = if n == 1 { "" } else { "s" };
+                        let message = format!("Exported {} playlist{} to {}", n, plural, path.display());
+                        self.handle_action(Action::Notice(message))?;
+                    }
+                    Err(e) => {
+                        self.handle_action(Action::Error(format!("Couldn't export playlists: {}", e)))?;
+                    }
+                }
+            }
+            Command::Messages => self.show_messages()?,
+            Command::Quit => self.handle_key(KeyBinding::Quit)?,
+        }
         Ok(())
     }
 
@@ -78,11 +359,86 @@ impl App {
                 exit(0);
             }
             KeyBinding::TogglePlayback => {
-                send_request(NetworkRequest::TogglePlayback);
+                self.store.send_request(NetworkRequest::TogglePlayback);
+            }
+            KeyBinding::NextTrack => {
+                self.store.send_request(NetworkRequest::NextTrack);
+            }
+            KeyBinding::PreviousTrack => {
+                self.store.send_request(NetworkRequest::PreviousTrack);
+            }
+            KeyBinding::SeekForward => {
+                self.store.send_request(NetworkRequest::SeekForward);
+            }
+            KeyBinding::SeekBackward => {
+                self.store.send_request(NetworkRequest::SeekBackward);
+            }
+            KeyBinding::SeekToPercent(percent) => {
+                self.store.send_request(NetworkRequest::SeekToPercent(percent));
+            }
+            KeyBinding::Restart => {
+                self.store.send_request(NetworkRequest::Restart);
+            }
+            KeyBinding::Devices => {
+                self.store.send_request(NetworkRequest::GetDevices);
+                self.add_screen(Box::new(DevicesScreen::new()))?;
+            }
+            KeyBinding::ToggleShuffle => {
+                self.store.send_request(NetworkRequest::ToggleShuffle);
+            }
+            KeyBinding::CycleRepeat => {
+                self.store.send_request(NetworkRequest::CycleRepeat);
+            }
+            KeyBinding::VolumeUp => {
+                let step = self.config.volume_step as i32;
+                self.muted_volume = None;
+                self.change_volume(|v| v as i32 + step)?;
             }
+            KeyBinding::VolumeDown => {
+                let step = self.config.volume_step as i32;
+                self.muted_volume = None;
+                self.change_volume(|v| v as i32 - step)?;
+            }
+            KeyBinding::ToggleMute => {
+                let volume = self.store.playback().as_ref().map(|p| p.volume_percent());
+                match (volume, self.muted_volume.take()) {
+                    (Some(0), Some(before)) => self.change_volume(|_| before as i32)?,
+                    (Some(v), _) if v != 0 => {
+                        self.muted_volume = Some(v);
+                        self.change_volume(|_| 0)?;
+                    }
+                    _ => {}
+                }
+            }
+            KeyBinding::Search => {
+                self.add_screen(Box::new(SearchScreen::new()))?;
+                let bounds = screen_bounds(&self.output)?;
+                let (screen, store) = self.screen_and_store();
+                if let Some(a) = screen.receive_input(store, key, bounds) {
+                    self.handle_action(a)?;
+                }
+            }
+            KeyBinding::CommandLine => {
+                // So `:device` can complete names
+                self.store.send_request(NetworkRequest::GetDevices);
+                self.command_line = Some(Prompt::new(":"));
+                self.redraw_status_bar()?;
+            }
+            KeyBinding::Find => {
+                if let Some(cursor) = self.current_screen_mut().cursor_mut() {
+                    cursor.start_find();
+                    self.find_prompt = Some(Prompt::new("/"));
+                    self.redraw_status_bar()?;
+                }
+            }
+            KeyBinding::Back => self.go_back()?,
+            KeyBinding::JumpBack => self.jump_back()?,
+            KeyBinding::JumpForward => self.jump_forward()?,
             _ => {
-                if let Some(a) = self.current_screen_mut().receive_input(key) {
-                    self.handle_action(a).unwrap();
+                let bounds = screen_bounds(&self.output)?;
+                let (screen, store) = self.screen_and_store();
+                if let Some(a) = screen.receive_input(store, key, bounds) {
+                    self.handle_action(a)?;
                 }
             }
         }
@@ -90,13 +446,23 @@ impl App {
     }
 
     pub fn redraw(&mut self) -> Result<()> {
-        queue!(stdout(), term::Clear(term::ClearType::All))?;
+        queue!(self.output, term::Clear(term::ClearType::All))?;
 
-        let (w, h) = term::size()?;
-        self.current_screen().display(
-            BoundingBox { x: 0, y: 0, width: w, height: h }
-        )?;
+        let bounds = screen_bounds(&self.output)?;
+        let screen = &self.screens[self.history[self.history_pos]];
+        screen.display(&self.store, &mut self.output, bounds)?;
+        self.redraw_status_bar()
+    }
 
+    pub fn redraw_status_bar(&mut self) -> Result<()> {
+        let bounds = status_bar_bounds(&self.output)?;
+        self.status_bar.display(&self.store, &mut self.output, bounds)?;
+        let bounds = prompt_bounds(&self.output)?;
+        if let Some(prompt) = self.find_prompt.as_ref().or(self.command_line.as_ref()) {
+            prompt.display(&mut self.output, bounds)?;
+        } else if let Some(ref message) = self.message {
+            self.status_bar.display_message(&mut self.output, bounds, message)?;
+        }
         Ok(())
     }
 
@@ -112,8 +478,29 @@ impl App {
             Action::Popup(popup) => {
                 self.display_popup(popup)?;
             },
+            Action::OpenPlaylist(id) => {
+                if self.store.playlists().contains_key(&id) {
+                    self.add_screen(Box::new(PlaylistScreen::new(id)))?;
+                } else {
+                    // It opens once it's in the store, see handle_store_event
+                    self.opening = Some(id.clone());
+                    self.store.send_request(NetworkRequest::LoadPlaylist(id));
+                }
+            }
+            Action::Tick => {
+                self.redraw_status_bar()?;
+            }
+            Action::Error(message) | Action::Notice(message) => {
+                if self.messages.len() == MAX_MESSAGES {
+                    self.messages.remove(0);
+                }
+                self.messages.push(message.clone());
+                self.message = Some(message);
+                self.redraw_status_bar()?;
+            }
             _ => {
-                if let Some(action) = self.current_screen_mut().notify(action) {
+                let (screen, store) = self.screen_and_store();
+                if let Some(action) = screen.notify(store, action) {
                     return self.handle_action(action);
                 }
             }
@@ -121,32 +508,264 @@ impl App {
         Ok(true)
     }
 
-    pub fn add_screen(&mut self, s: Box<dyn Screen + Send + Sync>) -> Result<()> {
-        self.screens.push(s);
+    // Every screen hears about every change, so one that isn't showing can carry on with a `G`
+    // or `/` that was waiting for more to load. Only the one showing gets drawn again
+    pub fn handle_store_event(&mut self, event: StoreEvent) -> Result<()> {
+        match event {
+            StoreEvent::PlaybackChanged => self.redraw_status_bar()?,
+            StoreEvent::PlaylistAdded(ref id) if self.opening.as_ref() == Some(id) => {
+                self.add_screen(Box::new(PlaylistScreen::new(id.clone())))?;
+            }
+            _ => {
+                let bounds = screen_bounds(&self.output)?;
+                let current = self.history[self.history_pos];
+                let mut actions = Vec::new();
+                for (i, screen) in self.screens.iter_mut().enumerate() {
+                    match screen.on_store_event(&self.store, &event, bounds) {
+                        Some(Action::Redraw) if i != current => {}
+                        Some(action) => actions.push(action),
+                        None => {}
+                    }
+                }
+                for action in actions {
+                    self.handle_action(action)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Shows the new volume right away, but waits for the volume keys to settle before sending it
+    fn change_volume(&mut self, f: impl FnOnce(u32) -> i32) -> Result<()> {
+        let volume = match *self.store.playback() {
+            Some(ref p) => f(p.volume_percent()).clamp(0, 100) as u32,
+            None => return Ok(()),
+        };
+        self.store.update_playback(|p| p.set_volume_percent(volume));
+        self.redraw_status_bar()?;
+
+        let change = self.volume_changes.fetch_add(1, Ordering::SeqCst) + 1;
+        let changes = Arc::clone(&self.volume_changes);
+        let store = Arc::clone(&self.store);
+        tokio::spawn(async move {
+            time::delay_for(VOLUME_DEBOUNCE).await;
+            if changes.load(Ordering::SeqCst) == change {
+                store.send_request(NetworkRequest::SetVolume(volume as u8));
+            }
+        });
+
+        Ok(())
+    }
+
+    // If a screen showing the same thing is already open, that one gets reused instead
+    pub fn add_screen(&mut self, s: Box<dyn Screen + Send>) -> Result<()> {
+        self.opening = None;
+        let id = s.id();
+        let index = match self.screens.iter().position(|s| s.id() == id) {
+            Some(i) => i,
+            None => {
+                self.screens.push(s);
+                self.screens.len() - 1
+            }
+        };
+
+        if self.history[self.history_pos] != index {
+            self.history.truncate(self.history_pos + 1);
+            self.history.push(index);
+            if self.history.len() > MAX_HISTORY {
+                self.history.remove(0);
+            }
+            self.history_pos = self.history.len() - 1;
+        }
+
         self.redraw()
     }
 
+    // Unlike jump_back, this forgets the current screen so jump_forward won't return to it
+    fn go_back(&mut self) -> Result<()> {
+        self.opening = None;
+        if self.history_pos == 0 {
+            return Ok(());
+        }
+
+        self.history.remove(self.history_pos);
+        self.history_pos -= 1;
+        if self.history.get(self.history_pos + 1) == Some(&self.history[self.history_pos]) {
+            self.history.remove(self.history_pos + 1);
+        }
+
+        self.redraw()
+    }
+
+    fn jump_back(&mut self) -> Result<()> {
+        self.opening = None;
+        if self.history_pos > 0 {
+            self.history_pos -= 1;
+            self.redraw()?;
+        }
+        Ok(())
+    }
+
+    fn jump_forward(&mut self) -> Result<()> {
+        self.opening = None;
+        if self.history_pos + 1 < self.history.len() {
+            self.history_pos += 1;
+            self.redraw()?;
+        }
+        Ok(())
+    }
+
     pub fn display_popup(&mut self, popup: Popup) -> Result<()> {
-        popup.display()?;
+        popup.display(&mut self.output)?;
         self.popup = Some(popup);
         Ok(())
     }
+
+    // Only the newest errors that fit on screen, like the end of vim's `:messages`
+    fn show_messages(&mut self) -> Result<()> {
+        if self.messages.is_empty() {
+            return self.display_popup(Popup::new(vec!["No messages".to_owned()]));
+        }
+        let (_, rows) = self.output.size()?;
+        // The popup starts a quarter of the way down, and has a border and a blank line around the text
+        let fits = std::cmp::max(rows.saturating_sub(rows / 4 + 4), 1) as usize;
+        let skip = self.messages.len().saturating_sub(fits);
+        self.display_popup(Popup::new(self.messages[skip..].to_vec()))
+    }
+
+    #[cfg(test)]
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    #[cfg(test)]
+    pub fn opening(&self) -> Option<&str> {
+        self.opening.as_deref()
+    }
+
+    #[cfg(test)]
+    pub fn screen_id(&self) -> ScreenId {
+        self.current_screen().id()
+    }
+
+    // Where the cursor is on the current screen, if it has one
+    #[cfg(test)]
+    pub fn selected_index(&mut self) -> Option<usize> {
+        self.current_screen_mut().cursor_mut().map(|c| c.index())
+    }
+
+    #[cfg(test)]
+    pub fn popup(&self) -> Option<&Popup> {
+        self.popup.as_ref()
+    }
 }
 
 #[derive(Debug)]
 pub enum Action {
-    PlaylistsUpdated,
+    OpenPlaylist(String),
+    // Search for this on the search screen, like pressing enter after typing it there
+    SubmitSearch(String),
+    Tick,
     Redraw,
     Quit,
     Key(KeyBinding),
     Popup(Popup),
+    // Something went wrong, shown to the user and kept for `:messages`
+    Error(String),
+    // The same, for something the user should know that isn't a failure, like going offline
+    Notice(String),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum NetworkRequest {
     LoadNextPage(PageId),
     LoadPlaylist(String),
     GetDevices,
+    // A device id, and whether to start playing on it
+    TransferPlayback(String, bool),
+    GetPlaybackStatus,
+    Search(String),
     TogglePlayback,
+    Play,
+    Pause,
     PlayUri(String),
+    // A context uri and optionally the position in it to start at
+    PlayContext(String, Option<u32>),
+    NextTrack,
+    PreviousTrack,
+    SeekForward,
+    SeekBackward,
+    SeekToPercent(u8),
+    // Back to the start of the track
+    Restart,
+    // Milliseconds from the current position
+    SeekBy(i64),
+    // A position in milliseconds
+    SeekTo(u32),
+    SetVolume(u8),
+    ToggleShuffle,
+    CycleRepeat,
+}
+
+impl NetworkRequest {
+    // What was being done, for error messages
+    pub fn description(&self) -> &'static str {
+        match self {
+            NetworkRequest::LoadNextPage(_) => "loading more",
+            NetworkRequest::LoadPlaylist(_) => "loading the playlist",
+            NetworkRequest::GetDevices => "getting devices",
+            NetworkRequest::TransferPlayback(..) => "switching devices",
+            NetworkRequest::GetPlaybackStatus => "getting what's playing",
+            NetworkRequest::Search(_) => "searching",
+            NetworkRequest::TogglePlayback => "toggling playback",
+            NetworkRequest::Play => "resuming playback",
+            NetworkRequest::Pause => "pausing",
+            NetworkRequest::PlayUri(_) | NetworkRequest::PlayContext(..) => "starting playback",
+            NetworkRequest::NextTrack => "skipping to the next track",
+            NetworkRequest::PreviousTrack => "going back a track",
+            NetworkRequest::SeekForward
+            | NetworkRequest::SeekBackward
+            | NetworkRequest::SeekToPercent(_)
+            | NetworkRequest::SeekBy(_)
+            | NetworkRequest::SeekTo(_) => "seeking",
+            NetworkRequest::Restart => "restarting the track",
+            NetworkRequest::SetVolume(_) => "setting the volume",
+            NetworkRequest::ToggleShuffle => "toggling shuffle",
+            NetworkRequest::CycleRepeat => "changing repeat",
+        }
+    }
+}
+
+// For tasks that have nowhere to pass an error on to. The app only fails if the terminal does,
+// and then there's nowhere left to show it anyway
+pub fn update<T>(app: &Mutex<App>, f: impl FnOnce(&mut App) -> Result<T>) {
+    f(&mut app.lock().unwrap()).ok();
+}
+
+// Passes what changes in the store on to the app, for as long as spotivi runs
+pub async fn watch_store(mut events: Events, app: Arc<Mutex<App>>) {
+    while let Some(event) = events.recv().await {
+        update(&app, |app| app.handle_store_event(event));
+    }
+}
+
+fn restore_terminal() -> Result<()> {
+    term::disable_raw_mode()?;
+    execute!(
+        stdout(),
+        term::LeaveAlternateScreen,
+        cursor::Show,
+    )?;
+    Ok(())
+}
+
+// A panic anywhere, even in a network task, would otherwise leave the terminal in raw mode
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal().ok();
+        default_hook(info);
+        // Whatever panicked is gone, so there's no carrying on without it
+        exit(101);
+    }));
 }
@@ -0,0 +1,86 @@
+// The playlists from the last run, kept on disk so they can be shown right away at startup
+// while Spotify is asked what changed. Spotify gives each version of a playlist a new
+// snapshot_id, so a saved playlist is only used while its summary still has the same one.
+// Anything missing or unreadable is treated as not saved, since it can always be fetched again
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::api::{Paged, Playlist, PlaylistSummary};
+
+pub fn load_summaries(dir: &Path) -> Option<Paged<PlaylistSummary>> {
+    load(&dir.join("playlists.json"))
+}
+
+pub fn save_summaries(dir: &Path, summaries: &Paged<PlaylistSummary>) -> Result<()> {
+    save(&dir.join("playlists.json"), summaries)
+}
+
+// Only if it's the version with `snapshot_id`
+pub fn load_playlist(dir: &Path, id: &str, snapshot_id: &str) -> Option<Playlist> {
+    load_any_playlist(dir, id).filter(|p| p.snapshot_id() == snapshot_id)
+}
+
+// Whichever version was saved last, for when there's no way to check
+pub fn load_any_playlist(dir: &Path, id: &str) -> Option<Playlist> {
+    load(&playlist_path(dir, id))
+}
+
+pub fn load_all_playlists(dir: &Path) -> Vec<Playlist> {
+    let entries = match fs::read_dir(dir.join("playlist")) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().map(|ext| ext == "json").unwrap_or(false))
+        .filter_map(|e| load(&e.path()))
+        .collect()
+}
+
+// Writes the saved playlists to `path` as text, for `:export`. Each playlist is its name and uri
+// separated by a tab, followed by a line for each of its saved tracks, indented with a tab.
+// Returns how many playlists there were
+pub fn export_playlists(dir: &Path, path: &Path) -> Result<usize> {
+    let mut playlists = load_all_playlists(dir);
+    playlists.sort_by(|a, b| a.name().cmp(b.name()));
+
+    let mut text = String::new();
+    for p in &playlists {
+        text.push_str(&format!("{}\t{}\n", p.name(), p.uri()));
+        for t in p.items().iter().filter_map(|t| t.track.as_ref()) {
+            text.push_str(&format!("\t{}\t{}\t{}\n", t.full_track.name, t.artists(), t.full_track.uri));
+        }
+    }
+    fs::write(path, text)?;
+    Ok(playlists.len())
+}
+
+pub fn save_playlist(dir: &Path, p: &Playlist) -> Result<()> {
+    save(&playlist_path(dir, p.id()), p)
+}
+
+fn playlist_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join("playlist").join(format!("{}.json", id))
+}
+
+fn load<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let text = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+// Written next to where it goes and then moved there, so quitting halfway through
+// can't leave half a file behind
+fn save<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string(value)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
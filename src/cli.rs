@@ -0,0 +1,218 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::api::{format_time, Device, MusicBackend, PlaybackState, SpotifyApi};
+use crate::command::{self, UriKind};
+use crate::config::Config;
+
+pub const USAGE: &str = "\
+Usage: spotivi [--offline | COMMAND]
+
+With no command, starts the interactive interface. If Spotify can't be reached it starts
+offline, with just the playlists saved from earlier.
+
+Commands:
+    play [URI]        Resume playback, or play a track, album, artist or playlist
+    pause             Pause playback
+    toggle            Pause or resume playback
+    next              Skip to the next track
+    previous          Go back to the previous track
+    status [--json]   Show what's playing
+    devices           List the devices that can play music
+    playlists         List your playlists
+    login             Log in to Spotify, which is needed before anything else
+
+Options:
+    --offline         Start offline even if Spotify can be reached
+    --check-config    Check the config file and print the settings that will be used
+    -h, --help        Show this message";
+
+#[derive(Debug, PartialEq)]
+pub enum Args {
+    Tui { offline: bool },
+    CheckConfig,
+    Login,
+    Help,
+    Command(CliCommand),
+}
+
+// Things that can be done without starting the interface, for scripts and hotkeys
+#[derive(Debug, PartialEq)]
+pub enum CliCommand {
+    Play(Option<String>),
+    Pause,
+    Toggle,
+    Next,
+    Previous,
+    Status { json: bool },
+    Devices,
+    Playlists,
+}
+
+impl CliCommand {
+    fn needs_device(&self) -> bool {
+        matches!(
+            self,
+            CliCommand::Play(_)
+                | CliCommand::Pause
+                | CliCommand::Toggle
+                | CliCommand::Next
+                | CliCommand::Previous
+        )
+    }
+
+    // Starting something new goes to the saved device, like in the interface. The rest are
+    // about what's already playing, which is on the active device if there is one
+    fn device_to_use(&self, devices: &[Device], saved_id: Option<String>) -> Option<String> {
+        let active = devices.iter().find(|d| d.is_active()).map(|d| d.id().to_owned());
+        match self {
+            CliCommand::Play(Some(_)) => saved_id.or(active),
+            _ => active.or(saved_id),
+        }
+    }
+}
+
+// `args` shouldn't include the program name
+pub fn parse_args(args: impl Iterator<Item = String>) -> Result<Args> {
+    let args: Vec<String> = args.collect();
+    let args: Vec<&str> = args.iter().map(|a| &a[..]).collect();
+
+    let parsed = match args[..] {
+        [] => Args::Tui { offline: false },
+        ["--offline"] => Args::Tui { offline: true },
+        ["--check-config"] => Args::CheckConfig,
+        ["login"] => Args::Login,
+        ["-h"] | ["--help"] | ["help"] => Args::Help,
+        ["play"] => Args::Command(CliCommand::Play(None)),
+        ["play", uri] => Args::Command(CliCommand::Play(Some(uri.to_owned()))),
+        ["pause"] => Args::Command(CliCommand::Pause),
+        ["toggle"] => Args::Command(CliCommand::Toggle),
+        ["next"] => Args::Command(CliCommand::Next),
+        ["previous"] | ["prev"] => Args::Command(CliCommand::Previous),
+        ["status"] => Args::Command(CliCommand::Status { json: false }),
+        ["status", "--json"] => Args::Command(CliCommand::Status { json: true }),
+        ["devices"] => Args::Command(CliCommand::Devices),
+        ["playlists"] => Args::Command(CliCommand::Playlists),
+        _ => bail!("Unknown arguments: {}\n\n{}", args.join(" "), USAGE),
+    };
+    Ok(parsed)
+}
+
+pub async fn run(command: CliCommand, config: Arc<Config>) -> Result<()> {
+    let mut api = SpotifyApi::new(Arc::clone(&config)).await?;
+
+    if command.needs_device() {
+        let devices = api.get_devices().await?;
+        let preferred = command.device_to_use(&devices, config.saved_device_id());
+        api.choose_device(&devices, preferred);
+        if api.device_id().is_none() {
+            bail!("There aren't any devices to play on");
+        }
+    }
+
+    match command {
+        CliCommand::Play(None) => api.resume_playback().await?,
+        CliCommand::Play(Some(uri)) => {
+            match command::parse_uri(&uri) {
+                Some((UriKind::Track, id)) => api.play_from_uri(command::uri(UriKind::Track, &id)).await?,
+                Some((kind, id)) => api.play_context(command::uri(kind, &id), None).await?,
+                None => bail!("Not a Spotify uri: {}", uri),
+            }
+        }
+        CliCommand::Pause => api.pause_playback().await?,
+        CliCommand::Toggle => {
+            let playing = api.playback_status().await?
+                .map(|p| p.is_playing())
+                .unwrap_or(false);
+            api.set_playing(playing);
+            api.toggle_playback().await?;
+        }
+        CliCommand::Next => api.next_track().await?,
+        CliCommand::Previous => api.previous_track().await?,
+        CliCommand::Status { json } => {
+            let playback = api.playback_status().await?;
+            if json {
+                // `null` means nothing is playing
+                println!("{}", playback.as_ref().map(PlaybackState::to_json).unwrap_or_default());
+            } else {
+                match playback {
+                    Some(p) => print_status(&p),
+                    None => println!("Nothing playing"),
+                }
+            }
+        }
+        CliCommand::Devices => {
+            for d in api.get_devices().await? {
+                let active = if d.is_active() { "*" } else { " " };
+                println!("{} {} ({}) {}%", active, d.name(), d.device_type(), d.volume_percent());
+            }
+        }
+        CliCommand::Playlists => {
+            let mut page = 0;
+            loop {
+                let playlists = api.get_playlists(page).await?;
+                // Tab separated so it's easy to cut out the uri
+                for p in playlists.items() {
+                    println!("{}\t{}", p.name(), command::uri(UriKind::Playlist, p.id()));
+                }
+                if playlists.next_page().is_none() {
+                    break;
+                }
+                page += 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_status(p: &PlaybackState) {
+    let state = if p.is_playing() { "playing" } else { "paused" };
+    match (p.title(), p.artists()) {
+        (Some(title), Some(artists)) => println!("{}: {} - {}", state, title, artists),
+        (Some(title), None) => println!("{}: {}", state, title),
+        _ => println!("{}", state),
+    }
+    println!(
+        "{} / {} on {} at {}%",
+        format_time(p.progress_ms()),
+        format_time(p.duration_ms()),
+        p.device_name(),
+        p.volume_percent(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Args> {
+        parse_args(args.split_whitespace().map(|a| a.to_owned()))
+    }
+
+    #[test]
+    fn parses_commands_and_options() {
+        let cases = [
+            ("", Args::Tui { offline: false }),
+            ("--offline", Args::Tui { offline: true }),
+            ("status", Args::Command(CliCommand::Status { json: false })),
+            ("status --json", Args::Command(CliCommand::Status { json: true })),
+            ("play", Args::Command(CliCommand::Play(None))),
+            ("play spotify:album:abc", Args::Command(CliCommand::Play(Some("spotify:album:abc".to_owned())))),
+            ("prev", Args::Command(CliCommand::Previous)),
+        ];
+        for (args, expected) in cases.iter() {
+            assert_eq!(&parse(args).unwrap(), expected, "spotivi {}", args);
+        }
+    }
+
+    #[test]
+    fn unknown_arguments_are_an_error() {
+        for args in &["shuffle", "status --yaml", "play one two", "--offline status"] {
+            let message = parse(args).unwrap_err().to_string();
+            assert!(message.starts_with(&format!("Unknown arguments: {}\n", args)), "{}", message);
+            assert!(message.ends_with(USAGE));
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::store::Store;
+
+const COMMANDS: &[&str] = &[
+    "device",
+    "export",
+    "messages",
+    "next",
+    "open",
+    "pause",
+    "play",
+    "previous",
+    "quit",
+    "search",
+    "volume",
+];
+
+#[derive(Debug)]
+pub enum Command {
+    Play,
+    Pause,
+    Next,
+    Previous,
+    // A device name
+    Device(String),
+    Volume(u8),
+    // Shows the errors so far
+    Messages,
+    Search(String),
+    Open(UriKind, String),
+    // Where to write the saved playlists
+    Export(PathBuf),
+    Quit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UriKind {
+    Track,
+    Album,
+    Artist,
+    Playlist,
+}
+
+impl UriKind {
+    fn from_name(name: &str) -> Option<UriKind> {
+        match name {
+            "track" => Some(UriKind::Track),
+            "album" => Some(UriKind::Album),
+            "artist" => Some(UriKind::Artist),
+            "playlist" => Some(UriKind::Playlist),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            UriKind::Track => "track",
+            UriKind::Album => "album",
+            UriKind::Artist => "artist",
+            UriKind::Playlist => "playlist",
+        }
+    }
+}
+
+pub fn uri(kind: UriKind, id: &str) -> String {
+    format!("spotify:{}:{}", kind.name(), id)
+}
+
+impl Command {
+    // Like vim, any unambiguous prefix of a command works, so `:vol 40` is `:volume 40`
+    pub fn parse(line: &str) -> Result<Command> {
+        let line = line.trim();
+        let (name, arg) = match line.find(' ') {
+            Some(i) => (&line[..i], line[i + 1..].trim()),
+            None => (line, ""),
+        };
+        let name = match name {
+            "q" => "quit",
+            _ => find_command(name)?,
+        };
+
+        let command = match name {
+            "play" => Command::Play,
+            "pause" => Command::Pause,
+            "next" => Command::Next,
+            "previous" => Command::Previous,
+            "quit" => Command::Quit,
+            "messages" => Command::Messages,
+            "device" if !arg.is_empty() => Command::Device(arg.to_owned()),
+            "search" if !arg.is_empty() => Command::Search(arg.to_owned()),
+            "export" if !arg.is_empty() => Command::Export(PathBuf::from(arg)),
+            "volume" => {
+                let volume: u8 = arg.parse().map_err(|_| anyhow!("Not a volume: {}", arg))?;
+                if volume > 100 {
+                    bail!("The volume has to be between 0 and 100");
+                }
+                Command::Volume(volume)
+            }
+            "open" => {
+                let (kind, id) = parse_uri(arg).ok_or_else(|| anyhow!("Not a Spotify uri: {}", arg))?;
+                Command::Open(kind, id)
+            }
+            _ => bail!("Missing an argument: {}", name),
+        };
+        Ok(command)
+    }
+}
+
+fn find_command(name: &str) -> Result<&'static str> {
+    if name.is_empty() {
+        bail!("No command given");
+    }
+    if let Some(c) = COMMANDS.iter().find(|c| **c == name) {
+        return Ok(*c);
+    }
+
+    let mut matches = COMMANDS.iter().filter(|c| c.starts_with(name));
+    match (matches.next(), matches.next()) {
+        (Some(c), None) => Ok(*c),
+        (None, _) => bail!("Not a command: {}", name),
+        _ => bail!("Ambiguous command: {}", name),
+    }
+}
+
+// Takes both `spotify:track:<id>` and `https://open.spotify.com/track/<id>`
+pub fn parse_uri(s: &str) -> Option<(UriKind, String)> {
+    let (kind, id) = if let Some(rest) = s.strip_prefix("spotify:") {
+        let mut parts = rest.splitn(2, ':');
+        (parts.next()?, parts.next()?)
+    } else {
+        let rest = s.strip_prefix("https://open.spotify.com/")?;
+        let mut parts = rest.splitn(2, '/');
+        let kind = parts.next()?;
+        // Links copied from the app have a query string on the end
+        (kind, parts.next()?.split('?').next()?)
+    };
+
+    if id.is_empty() {
+        return None;
+    }
+    Some((UriKind::from_name(kind)?, id.to_owned()))
+}
+
+// Whole lines that what's been typed could be completed to
+pub fn complete(line: &str, store: &Store) -> Vec<String> {
+    let (name, arg) = match line.find(' ') {
+        Some(i) => (&line[..i], line[i + 1..].trim_start()),
+        None => {
+            return COMMANDS.iter()
+                .filter(|c| c.starts_with(line))
+                .map(|c| c.to_string())
+                .collect();
+        }
+    };
+
+    match find_command(name) {
+        Ok("device") => {
+            let arg = arg.to_lowercase();
+            store.devices()
+                .items()
+                .iter()
+                .filter(|d| d.name().to_lowercase().starts_with(&arg))
+                .map(|d| format!("device {}", d.name()))
+                .collect()
+        }
+        // Your playlists, found by the start of the uri or the name. Anything else that can be
+        // opened would have to be searched for first, which `:search` is for
+        Ok("open") => {
+            let name = arg.to_lowercase();
+            store.playlist_summaries()
+                .items()
+                .iter()
+                .map(|p| (p.name(), uri(UriKind::Playlist, p.id())))
+                .filter(|(n, uri)| uri.starts_with(arg) || n.to_lowercase().starts_with(&name))
+                .map(|(_, uri)| format!("open {}", uri))
+                .collect()
+        }
+        // The rest take something new rather than something spotivi knows about: a number, a
+        // query or a file to write
+        _ => Vec::new(),
+    }
+}
+
+// Cycles through the completions of what was typed, and then back to what was typed
+#[derive(Debug)]
+pub struct Completion {
+    typed: String,
+    candidates: Vec<String>,
+    index: Option<usize>,
+}
+
+impl Completion {
+    pub fn new(typed: &str, store: &Store) -> Completion {
+        Completion {
+            typed: typed.to_owned(),
+            candidates: complete(typed, store),
+            index: None,
+        }
+    }
+
+    pub fn next(&mut self, forward: bool) -> &str {
+        let len = self.candidates.len();
+        self.index = match (self.index, forward) {
+            _ if len == 0 => None,
+            (None, true) => Some(0),
+            (None, false) => Some(len - 1),
+            (Some(i), true) if i + 1 < len => Some(i + 1),
+            (Some(i), false) if i > 0 => Some(i - 1),
+            _ => None,
+        };
+
+        match self.index {
+            Some(i) => &self.candidates[i],
+            None => &self.typed,
+        }
+    }
+}
+
+// Lines entered with `:`, kept between runs
+#[derive(Debug)]
+pub struct CommandHistory {
+    path: PathBuf,
+    lines: Vec<String>,
+    max_len: usize,
+    // Where up and down are in `lines`, which is `lines.len()` when not looking through it
+    pos: usize,
+    // What was typed before pressing up
+    draft: String,
+}
+
+impl CommandHistory {
+    pub fn load(path: PathBuf, max_len: usize) -> CommandHistory {
+        let lines: Vec<_> = fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_owned())
+            .collect();
+        CommandHistory {
+            path,
+            pos: lines.len(),
+            lines,
+            max_len,
+            draft: String::new(),
+        }
+    }
+
+    pub fn push(&mut self, line: &str) -> Result<()> {
+        // Running the same thing again moves it to the end instead of adding it twice
+        self.lines.retain(|l| l != line);
+        self.lines.push(line.to_owned());
+        if self.lines.len() > self.max_len {
+            self.lines.drain(..self.lines.len() - self.max_len);
+        }
+        self.reset();
+
+        let mut contents = self.lines.join("\n");
+        contents.push('\n');
+        fs::write(&self.path, contents)?;
+        Ok(())
+    }
+
+    pub fn reset(&mut self) {
+        self.pos = self.lines.len();
+    }
+
+    pub fn older(&mut self, current: &str) -> Option<&str> {
+        if self.pos == 0 {
+            return None;
+        }
+        if self.pos == self.lines.len() {
+            self.draft = current.to_owned();
+        }
+        self.pos -= 1;
+        Some(&self.lines[self.pos])
+    }
+
+    pub fn newer(&mut self) -> Option<&str> {
+        if self.pos >= self.lines.len() {
+            return None;
+        }
+        self.pos += 1;
+        if self.pos == self.lines.len() {
+            Some(&self.draft)
+        } else {
+            Some(&self.lines[self.pos])
+        }
+    }
+}
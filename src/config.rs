@@ -1,36 +1,97 @@
-use std::{
-    io::{BufReader, BufRead, Read},
-    path::PathBuf,
-};
-use std::fs::File;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
 
 use anyhow::Result;
-use regex::Regex;
+use serde::{Deserialize, Serialize};
 use xdg::BaseDirectories;
 
-use crate::keybindings::{self, KeyBindings};
+use crate::keybindings::{self, format_keys, parse_keys, KeyBinding, KeyBindings};
 
 pub struct Config {
     pub api_client_id: String,
     pub api_client_secret: String,
     pub redirect_uri: String,
+    pub seek_seconds: u32,
+    pub volume_step: u32,
+    pub command_history_size: usize,
 
     pub keybindings: KeyBindings,
+    pub path: PathBuf,
     pub cache_path: PathBuf,
+    pub device_cache_path: PathBuf,
+    // Playlists from the last run, see cache.rs
+    pub library_cache_dir: PathBuf,
+    pub command_history_path: PathBuf,
+    // Where the control socket goes, if there's an XDG runtime dir for it
+    pub socket_path: Option<PathBuf>,
+    // Problems with the config file that weren't bad enough to stop for
+    pub warnings: Vec<String>,
     xdg_dirs: BaseDirectories,
 }
 
+// The layout of config.toml. Everything is optional, anything left out keeps its default
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+struct ConfigFile {
+    api: ApiSection,
+    playback: PlaybackSection,
+    ui: UiSection,
+    // Keys in vim's notation and the action for them, or "none" to unmap them
+    keys: BTreeMap<String, String>,
+    #[serde(flatten, skip_serializing)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+struct ApiSection {
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    redirect_uri: Option<String>,
+    #[serde(flatten, skip_serializing)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+struct PlaybackSection {
+    seek_seconds: Option<u32>,
+    volume_step: Option<u32>,
+    #[serde(flatten, skip_serializing)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+struct UiSection {
+    command_history_size: Option<usize>,
+    #[serde(flatten, skip_serializing)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
 impl Config {
     pub fn new() -> Result<Config> {
         let mut conf = Config::default()?;
+        // These go first so the [keys] section can change them
+        keybindings::default_keybindings(&mut conf.keybindings);
 
-        let config_path = conf.xdg_dirs.place_config_file("config")?;
-        if config_path.exists() {
-            let mut config_file = File::open(config_path)?;
-            conf.read_config_file(&mut config_file)?;
+        if conf.path.exists() {
+            let text = fs::read_to_string(&conf.path)?;
+            conf.read_config(&text)
+                .map_err(|e| anyhow!("{}: {}", conf.path.display(), e))?;
+        } else if let Some(old_path) = conf.xdg_dirs.find_config_file("config") {
+            conf.warnings.push(format!(
+                "{} isn't read anymore, its settings go in {} now",
+                old_path.display(),
+                conf.path.display(),
+            ));
         }
 
-        keybindings::default_keybindings(&mut conf.keybindings);
+        if conf.api_client_id.is_empty() || conf.api_client_secret.is_empty() {
+            conf.warnings.push("api.client_id and api.client_secret need to be set".to_owned());
+        }
 
         Ok(conf)
     }
@@ -42,29 +103,152 @@ impl Config {
             api_client_id: String::new(),
             api_client_secret: String::new(),
             redirect_uri: "http://localhost:8888/callback".to_owned(),
+            seek_seconds: 10,
+            volume_step: 5,
+            command_history_size: 100,
             keybindings: KeyBindings::new(),
+            path: xdg_dirs.place_config_file("config.toml")?,
             cache_path: xdg_dirs.place_cache_file("api_auth.json")?,
+            device_cache_path: xdg_dirs.place_cache_file("device")?,
+            library_cache_dir: xdg_dirs.get_cache_home().join("library"),
+            command_history_path: xdg_dirs.place_data_file("command_history")?,
+            socket_path: xdg_dirs.place_runtime_file("control.sock").ok(),
+            warnings: Vec::new(),
             xdg_dirs,
         })
     }
 
-    fn read_config_file<R: Read>(&mut self, config: &mut R) -> Result<()> {
-        let re = Regex::new(r"^\s*([^ =]+)\s*=\s*([^ =]+)\s*$").unwrap();
-        for line in BufReader::new(config).lines() {
-            match re.captures(&line?[..]) {
-                Some(caps) => {
-                    let key = &caps[1];
-                    let value = &caps[2];
-                    match key {
-                        "api_client_id" => self.api_client_id = value.to_owned(),
-                        "api_client_secret" => self.api_client_secret = value.to_owned(),
-                        "redirect_uri" => self.redirect_uri = value.to_owned(),
-                        _ => todo!(),
-                    }
-                }
-                None => todo!(),
+    // The defaults, with every file spotivi writes kept inside `dir`
+    #[cfg(test)]
+    pub fn for_tests(dir: &std::path::Path) -> Result<Config> {
+        let mut conf = Config::default()?;
+        keybindings::default_keybindings(&mut conf.keybindings);
+        conf.path = dir.join("config.toml");
+        conf.cache_path = dir.join("api_auth.json");
+        conf.device_cache_path = dir.join("device");
+        conf.library_cache_dir = dir.join("library");
+        conf.command_history_path = dir.join("command_history");
+        conf.socket_path = None;
+        Ok(conf)
+    }
+
+    // The last device playback was transferred to, so it can be used again next time
+    pub fn saved_device_id(&self) -> Option<String> {
+        fs::read_to_string(&self.device_cache_path)
+            .ok()
+            .map(|s| s.trim().to_owned())
+            .filter(|s| !s.is_empty())
+    }
+
+    pub fn save_device_id(&self, id: &str) -> Result<()> {
+        fs::write(&self.device_cache_path, id)?;
+        Ok(())
+    }
+
+    // Syntax and type errors come from the toml crate, which already says which line they're on
+    fn read_config(&mut self, text: &str) -> Result<()> {
+        let file: ConfigFile = toml::from_str(text)?;
+
+        for (name, value) in file.unknown.iter() {
+            let kind = if value.is_table() { "section" } else { "setting" };
+            self.warnings.push(at_line(text, None, name, format!("Unknown {}: {}", kind, name)));
+        }
+        for &(section, unknown) in [
+            ("api", &file.api.unknown),
+            ("playback", &file.playback.unknown),
+            ("ui", &file.ui.unknown),
+        ].iter() {
+            for key in unknown.keys() {
+                self.warnings.push(at_line(text, Some(section), key, format!("Unknown setting: {}.{}", section, key)));
             }
         }
+
+        if let Some(id) = file.api.client_id {
+            self.api_client_id = id;
+        }
+        if let Some(secret) = file.api.client_secret {
+            self.api_client_secret = secret;
+        }
+        if let Some(uri) = file.api.redirect_uri {
+            self.redirect_uri = uri;
+        }
+
+        if let Some(seconds) = file.playback.seek_seconds {
+            if seconds == 0 {
+                bail!(at_line(text, Some("playback"), "seek_seconds", "seek_seconds has to be more than 0"));
+            }
+            self.seek_seconds = seconds;
+        }
+        if let Some(step) = file.playback.volume_step {
+            if step == 0 || step > 100 {
+                bail!(at_line(text, Some("playback"), "volume_step", "volume_step has to be from 1 to 100"));
+            }
+            self.volume_step = step;
+        }
+
+        if let Some(size) = file.ui.command_history_size {
+            self.command_history_size = size;
+        }
+
+        for (keys, action) in file.keys.iter() {
+            let error = |e: anyhow::Error| anyhow!(at_line(text, Some("keys"), keys, e));
+            let sequence = parse_keys(keys).map_err(error)?;
+            if action == "none" {
+                self.keybindings.remove(&sequence);
+            } else {
+                let binding = KeyBinding::from_name(action).map_err(error)?;
+                self.keybindings.insert(sequence, binding);
+            }
+        }
+
         Ok(())
     }
+
+    // What `--check-config` prints, with the defaults filled in
+    pub fn to_toml(&self) -> Result<String> {
+        let secret = if self.api_client_secret.is_empty() { "" } else { "<hidden>" };
+        let file = ConfigFile {
+            api: ApiSection {
+                client_id: Some(self.api_client_id.clone()),
+                client_secret: Some(secret.to_owned()),
+                redirect_uri: Some(self.redirect_uri.clone()),
+                unknown: BTreeMap::new(),
+            },
+            playback: PlaybackSection {
+                seek_seconds: Some(self.seek_seconds),
+                volume_step: Some(self.volume_step),
+                unknown: BTreeMap::new(),
+            },
+            ui: UiSection {
+                command_history_size: Some(self.command_history_size),
+                unknown: BTreeMap::new(),
+            },
+            keys: self.keybindings.iter()
+                .map(|(keys, binding)| (format_keys(keys), binding.name()))
+                .collect(),
+            unknown: BTreeMap::new(),
+        };
+        toml::to_string(&file).map_err(|e| anyhow!(e))
+    }
+}
+
+// Puts the line `key` is on in `section` (or at the top level) in front of the message, if it can be found
+fn at_line(text: &str, section: Option<&str>, key: &str, message: impl Display) -> String {
+    let mut current = None;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            current = Some(line.trim_matches(|c| c == '[' || c == ']').trim());
+            if section.is_none() && current == Some(key) {
+                return format!("line {}: {}", i + 1, message);
+            }
+            continue;
+        }
+
+        let name = line.split('=').next().unwrap_or("").trim().trim_matches(|c| c == '"' || c == '\'');
+        if current == section && name == key {
+            return format!("line {}: {}", i + 1, message);
+        }
+    }
+    message.to_string()
 }
@@ -0,0 +1,264 @@
+// A Unix socket other programs can use to control a running spotivi.
+//
+// Clients send one JSON object per line, like
+//     {"command": "toggle"}
+//     {"command": "play", "uri": "spotify:track:..."}
+//     {"command": "open_playlist", "id": "..."}
+// and get one line back for each, like {"ok": true, "state": {...}} once the command is on its
+// way to Spotify. The state is what was playing when the command came in (or null), so it won't
+// show what the command did yet. After {"command": "subscribe"} the connection only gets events,
+// like {"event": "playback", "state": {...}}, whenever the playback state changes, which is how
+// to see that.
+
+use std::fs;
+use std::os::unix::net::UnixStream as StdUnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::broadcast;
+
+use crate::app::{Action, App, NetworkRequest};
+use crate::command::{self, UriKind};
+use crate::store::{Events, Store, StoreEvent};
+
+// The socket file this instance made, so quitting removes it but never another instance's
+pub struct Socket {
+    path: PathBuf,
+}
+
+impl Socket {
+    pub fn remove(&self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum ControlRequest {
+    Toggle,
+    Play { uri: Option<String> },
+    Pause,
+    Next,
+    Previous,
+    OpenPlaylist { id: String },
+    State,
+    Subscribe,
+}
+
+// Fails if another instance is already listening on `path`
+pub fn bind(path: &Path) -> Result<(UnixListener, Socket)> {
+    if path.exists() {
+        if StdUnixStream::connect(path).is_ok() {
+            bail!("Another spotivi is already listening on {}", path.display());
+        }
+        // Left over from an instance that didn't get to clean up
+        fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    Ok((listener, Socket { path: path.to_owned() }))
+}
+
+pub async fn serve(mut listener: UnixListener, app: Arc<Mutex<App>>, store: Arc<Store>) {
+    let (events, _) = broadcast::channel(16);
+    tokio::spawn(watch_playback(store.subscribe(), Arc::clone(&store), events.clone()));
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(_) => continue,
+        };
+        let app = Arc::clone(&app);
+        let store = Arc::clone(&store);
+        let events = events.clone();
+        tokio::spawn(async move {
+            // A client going away in the middle of something isn't a problem
+            handle_client(stream, app, store, events).await.ok();
+        });
+    }
+}
+
+// Passes playback changes on to the subscribed clients, for as long as spotivi runs
+async fn watch_playback(mut store_events: Events, store: Arc<Store>, events: broadcast::Sender<String>) {
+    // The last state sent, without the progress, which changes all the time
+    let mut last = Value::Null;
+    while let Some(event) = store_events.recv().await {
+        if let StoreEvent::PlaybackChanged = event {
+            let state = current_state(&store);
+            let mut without_progress = state.clone();
+            if let Some(obj) = without_progress.as_object_mut() {
+                obj.remove("progress_ms");
+            }
+
+            if last != without_progress {
+                last = without_progress;
+                // This only fails when nobody is subscribed
+                events.send(json!({ "event": "playback", "state": state }).to_string()).ok();
+            }
+        }
+    }
+}
+
+fn current_state(store: &Store) -> Value {
+    store.playback()
+        .as_ref()
+        .map(|p| p.to_json())
+        .unwrap_or(Value::Null)
+}
+
+async fn handle_client(
+    stream: UnixStream,
+    app: Arc<Mutex<App>>,
+    store: Arc<Store>,
+    events: broadcast::Sender<String>,
+) -> Result<()> {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str(&line) {
+            Ok(ControlRequest::Subscribe) => {
+                let events = events.subscribe();
+                write_line(&mut writer, &json!({ "ok": true, "state": current_state(&store) })).await?;
+                return send_events(events, writer).await;
+            }
+            // Taken before the command is handled, since it has only been sent by the time
+            // this replies and the state wouldn't show it yet anyway
+            Ok(request) => {
+                let state = current_state(&store);
+                match handle_request(request, &app, &store) {
+                    Ok(()) => json!({ "ok": true, "state": state }),
+                    Err(e) => json!({ "ok": false, "error": e.to_string(), "state": state }),
+                }
+            }
+            Err(e) => json!({ "ok": false, "error": e.to_string(), "state": current_state(&store) }),
+        };
+        write_line(&mut writer, &reply).await?;
+    }
+
+    Ok(())
+}
+
+fn handle_request(request: ControlRequest, app: &Mutex<App>, store: &Store) -> Result<()> {
+    match request {
+        ControlRequest::Toggle => store.send_request(NetworkRequest::TogglePlayback),
+        ControlRequest::Play { uri: None } => store.send_request(NetworkRequest::Play),
+        ControlRequest::Play { uri: Some(uri) } => match command::parse_uri(&uri) {
+            Some((UriKind::Track, id)) => store.send_request(NetworkRequest::PlayUri(command::uri(UriKind::Track, &id))),
+            Some((kind, id)) => store.send_request(NetworkRequest::PlayContext(command::uri(kind, &id), None)),
+            None => bail!("Not a Spotify uri: {}", uri),
+        },
+        ControlRequest::Pause => store.send_request(NetworkRequest::Pause),
+        ControlRequest::Next => store.send_request(NetworkRequest::NextTrack),
+        ControlRequest::Previous => store.send_request(NetworkRequest::PreviousTrack),
+        ControlRequest::OpenPlaylist { id } => {
+            app.lock().unwrap().handle_action(Action::OpenPlaylist(id))?;
+        }
+        ControlRequest::State | ControlRequest::Subscribe => {}
+    }
+    Ok(())
+}
+
+async fn send_events(mut events: broadcast::Receiver<String>, mut writer: impl AsyncWrite + Unpin) -> Result<()> {
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                writer.write_all(event.as_bytes()).await?;
+                writer.write_all(b"\n").await?;
+            }
+            // A slow client misses some events, but the next one has the whole state anyway
+            Err(broadcast::RecvError::Lagged(_)) => {}
+            Err(broadcast::RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+async fn write_line(writer: &mut (impl AsyncWrite + Unpin), value: &Value) -> Result<()> {
+    writer.write_all(value.to_string().as_bytes()).await?;
+    writer.write_all(b"\n").await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{Lines, ReadHalf, WriteHalf};
+
+    use super::*;
+    use crate::config::Config;
+    use crate::tests::{road_trip_playing, temp_dir};
+    use crate::views::{CellGrid, Output};
+
+    struct Client {
+        lines: Lines<BufReader<ReadHalf<UnixStream>>>,
+        writer: WriteHalf<UnixStream>,
+    }
+
+    impl Client {
+        async fn connect(path: &Path) -> Client {
+            let (reader, writer) = tokio::io::split(UnixStream::connect(path).await.unwrap());
+            Client {
+                lines: BufReader::new(reader).lines(),
+                writer,
+            }
+        }
+
+        async fn send(&mut self, line: &str) -> Value {
+            self.writer.write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+            self.next().await
+        }
+
+        async fn next(&mut self) -> Value {
+            let line = self.lines.next_line().await.unwrap().unwrap();
+            serde_json::from_str(&line).unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn commands_over_the_socket() {
+        let dir = temp_dir();
+        let path = dir.join("control.sock");
+        // Left behind by an instance that didn't get to clean up, so nothing is listening on it
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+        let (listener, socket) = bind(&path).unwrap();
+        let (store, mut requests) = Store::new();
+        let store = Arc::new(store);
+        let config = Arc::new(Config::for_tests(&dir).unwrap());
+        let grid = Arc::new(Mutex::new(CellGrid::new(80, 24)));
+        let app = Arc::new(Mutex::new(App::new(config, Arc::clone(&store), Output::Grid(grid))));
+        tokio::spawn(serve(listener, app, Arc::clone(&store)));
+        let mut client = Client::connect(&path).await;
+
+        assert_eq!(client.send(r#"{"command": "toggle"}"#).await, json!({ "ok": true, "state": null }));
+        match requests.recv().await {
+            Some(NetworkRequest::TogglePlayback) => {}
+            r => panic!("expected TogglePlayback, got {:?}", r),
+        }
+
+        store.set_playback(Some(road_trip_playing().await));
+        // The progress moves on while playing, so not all of the state can be compared
+        let reply = client.send(r#"{"command": "state"}"#).await;
+        assert_eq!(reply["ok"], json!(true));
+        assert_eq!(reply["state"]["title"], json!("Highway Song"));
+
+        let reply = client.send("{not json").await;
+        assert_eq!(reply["ok"], json!(false));
+        assert!(reply["error"].is_string());
+
+        let mut subscriber = Client::connect(&path).await;
+        assert_eq!(subscriber.send(r#"{"command": "subscribe"}"#).await["state"]["title"], json!("Highway Song"));
+        store.set_playback(None);
+        assert_eq!(subscriber.next().await, json!({ "event": "playback", "state": null }));
+
+        socket.remove();
+        assert!(!path.exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,10 +1,12 @@
 use std::collections::HashMap;
 
+use anyhow::Result;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
-pub type KeyBindings = HashMap<KeyEvent, KeyBinding>;
+// Keys are sequences, like vim's `gg`
+pub type KeyBindings = HashMap<Vec<KeyEvent>, KeyBinding>;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum KeyBinding {
     Up,
     Down,
@@ -12,6 +14,320 @@ pub enum KeyBinding {
     Quit,
     InfoPopup,
     TogglePlayback,
+    Back,
+    JumpBack,
+    JumpForward,
+    NextTrack,
+    PreviousTrack,
+    SeekForward,
+    SeekBackward,
+    SeekToPercent(u8),
+    Restart,
+    Devices,
+    VolumeUp,
+    VolumeDown,
+    ToggleMute,
+    ToggleShuffle,
+    CycleRepeat,
+    Search,
+    NextGroup,
+    PrevGroup,
+    Find,
+    FindNext,
+    FindPrev,
+    Top,
+    Bottom,
+    HalfPageDown,
+    HalfPageUp,
+    PageDown,
+    PageUp,
+    ScreenTop,
+    ScreenMiddle,
+    ScreenBottom,
+    CommandLine,
+}
+
+// The names used for actions in the config file
+const ACTION_NAMES: &[(&str, KeyBinding)] = &[
+    ("up", KeyBinding::Up),
+    ("down", KeyBinding::Down),
+    ("enter", KeyBinding::Enter),
+    ("quit", KeyBinding::Quit),
+    ("info_popup", KeyBinding::InfoPopup),
+    ("toggle_playback", KeyBinding::TogglePlayback),
+    ("back", KeyBinding::Back),
+    ("jump_back", KeyBinding::JumpBack),
+    ("jump_forward", KeyBinding::JumpForward),
+    ("next_track", KeyBinding::NextTrack),
+    ("previous_track", KeyBinding::PreviousTrack),
+    ("seek_forward", KeyBinding::SeekForward),
+    ("seek_backward", KeyBinding::SeekBackward),
+    ("restart", KeyBinding::Restart),
+    ("devices", KeyBinding::Devices),
+    ("volume_up", KeyBinding::VolumeUp),
+    ("volume_down", KeyBinding::VolumeDown),
+    ("toggle_mute", KeyBinding::ToggleMute),
+    ("toggle_shuffle", KeyBinding::ToggleShuffle),
+    ("cycle_repeat", KeyBinding::CycleRepeat),
+    ("search", KeyBinding::Search),
+    ("next_group", KeyBinding::NextGroup),
+    ("prev_group", KeyBinding::PrevGroup),
+    ("find", KeyBinding::Find),
+    ("find_next", KeyBinding::FindNext),
+    ("find_prev", KeyBinding::FindPrev),
+    ("top", KeyBinding::Top),
+    ("bottom", KeyBinding::Bottom),
+    ("half_page_down", KeyBinding::HalfPageDown),
+    ("half_page_up", KeyBinding::HalfPageUp),
+    ("page_down", KeyBinding::PageDown),
+    ("page_up", KeyBinding::PageUp),
+    ("screen_top", KeyBinding::ScreenTop),
+    ("screen_middle", KeyBinding::ScreenMiddle),
+    ("screen_bottom", KeyBinding::ScreenBottom),
+    ("command_line", KeyBinding::CommandLine),
+];
+
+impl KeyBinding {
+    // Like `seek_to_percent 50` or `toggle_playback`
+    pub fn from_name(action: &str) -> Result<KeyBinding> {
+        let mut words = action.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let binding = match name {
+            "seek_to_percent" => {
+                let percent = words.next()
+                    .and_then(|p| p.parse().ok())
+                    .filter(|&p| p <= 100)
+                    .ok_or_else(|| anyhow!("seek_to_percent needs a percent from 0 to 100"))?;
+                KeyBinding::SeekToPercent(percent)
+            }
+            _ => ACTION_NAMES.iter()
+                .find(|(n, _)| *n == name)
+                .map(|&(_, b)| b)
+                .ok_or_else(|| anyhow!("Unknown action: {}", name))?,
+        };
+
+        if let Some(extra) = words.next() {
+            bail!("Unexpected argument to {}: {}", name, extra);
+        }
+        Ok(binding)
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            KeyBinding::SeekToPercent(percent) => format!("seek_to_percent {}", percent),
+            _ => ACTION_NAMES.iter()
+                .find(|(_, b)| b == self)
+                .map(|(n, _)| n.to_string())
+                .unwrap(),
+        }
+    }
+}
+
+// Parses vim's key notation, like `gg`, `<C-d>` and `<S-Tab>`
+pub fn parse_keys(s: &str) -> Result<Vec<KeyEvent>> {
+    let mut keys = Vec::new();
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        let special = if c == '<' { rest.find('>') } else { None };
+        match special {
+            // `<` on its own is just `<`, like in vim
+            Some(end) if end > 1 => {
+                keys.push(parse_special_key(&rest[1..end])?);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                keys.push(key_with_mods(c, KeyModifiers::NONE));
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    if keys.is_empty() {
+        bail!("No keys given");
+    }
+    Ok(keys)
+}
+
+// The inside of something like `<C-S-x>`
+fn parse_special_key(s: &str) -> Result<KeyEvent> {
+    let mut mods = KeyModifiers::NONE;
+    let mut name = s;
+    while name.len() > 2 && name.as_bytes()[1] == b'-' {
+        mods |= match name.as_bytes()[0].to_ascii_uppercase() {
+            b'C' => KeyModifiers::CONTROL,
+            b'S' => KeyModifiers::SHIFT,
+            b'A' | b'M' => KeyModifiers::ALT,
+            _ => bail!("Unknown modifier in <{}>", s),
+        };
+        name = &name[2..];
+    }
+
+    let code = match &name.to_lowercase()[..] {
+        "cr" | "enter" | "return" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" if mods.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+        "tab" => KeyCode::Tab,
+        "bs" | "backspace" => KeyCode::Backspace,
+        "del" | "delete" => KeyCode::Delete,
+        "space" => KeyCode::Char(' '),
+        "lt" => KeyCode::Char('<'),
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "insert" => KeyCode::Insert,
+        f if f.starts_with('f') && f.len() > 1 => match f[1..].parse() {
+            Ok(n) if (1..=12).contains(&n) => KeyCode::F(n),
+            _ => bail!("Unknown key: <{}>", s),
+        },
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => return Ok(key_with_mods(c, mods)),
+                _ => bail!("Unknown key: <{}>", s),
+            }
+        }
+    };
+    // The shift is part of BackTab, crossterm doesn't report it again
+    if code == KeyCode::BackTab {
+        mods.remove(KeyModifiers::SHIFT);
+    }
+    Ok(KeyEvent::new(code, mods))
+}
+
+// The opposite of parse_keys
+pub fn format_keys(keys: &[KeyEvent]) -> String {
+    keys.iter().map(|&k| format_key(k)).collect()
+}
+
+fn format_key(key: KeyEvent) -> String {
+    let name = match key.code {
+        KeyCode::Enter => "CR".to_owned(),
+        KeyCode::Esc => "Esc".to_owned(),
+        KeyCode::Tab => "Tab".to_owned(),
+        KeyCode::BackTab => "Tab".to_owned(),
+        KeyCode::Backspace => "BS".to_owned(),
+        KeyCode::Delete => "Del".to_owned(),
+        KeyCode::Up => "Up".to_owned(),
+        KeyCode::Down => "Down".to_owned(),
+        KeyCode::Left => "Left".to_owned(),
+        KeyCode::Right => "Right".to_owned(),
+        KeyCode::Home => "Home".to_owned(),
+        KeyCode::End => "End".to_owned(),
+        KeyCode::PageUp => "PageUp".to_owned(),
+        KeyCode::PageDown => "PageDown".to_owned(),
+        KeyCode::Insert => "Insert".to_owned(),
+        KeyCode::F(n) => format!("F{}", n),
+        KeyCode::Char(' ') => "Space".to_owned(),
+        KeyCode::Char('<') => "lt".to_owned(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Null => "Nul".to_owned(),
+    };
+
+    // Shift is already in uppercase letters, and BackTab is shift-tab
+    let mut mods = key.modifiers;
+    match key.code {
+        KeyCode::Char(_) => mods.remove(KeyModifiers::SHIFT),
+        KeyCode::BackTab => mods.insert(KeyModifiers::SHIFT),
+        _ => {}
+    }
+    let mut prefix = String::new();
+    if mods.contains(KeyModifiers::CONTROL) {
+        prefix.push_str("C-");
+    }
+    if mods.contains(KeyModifiers::SHIFT) {
+        prefix.push_str("S-");
+    }
+    if mods.contains(KeyModifiers::ALT) {
+        prefix.push_str("A-");
+    }
+
+    match key.code {
+        KeyCode::Char(c) if prefix.is_empty() && c != ' ' && c != '<' => name,
+        _ => format!("<{}{}>", prefix, name),
+    }
+}
+
+// Makes a key look the way crossterm reports it: uppercase letters come with shift,
+// and so does shift with a letter. Ctrl letters are lowercase without shift, and alt letters
+// keep their case but never have shift
+fn key_with_mods(c: char, mut mods: KeyModifiers) -> KeyEvent {
+    let mut c = c;
+    if mods.contains(KeyModifiers::CONTROL) {
+        c = c.to_ascii_lowercase();
+        mods.remove(KeyModifiers::SHIFT);
+    } else if mods.contains(KeyModifiers::ALT) {
+        if mods.contains(KeyModifiers::SHIFT) {
+            c = c.to_ascii_uppercase();
+        }
+        mods.remove(KeyModifiers::SHIFT);
+    } else if c.is_ascii_uppercase() || (mods.contains(KeyModifiers::SHIFT) && c.is_ascii_lowercase()) {
+        c = c.to_ascii_uppercase();
+        mods.insert(KeyModifiers::SHIFT);
+    }
+    KeyEvent::new(KeyCode::Char(c), mods)
+}
+
+// Collects keys until they make up a binding, with an optional count in front, like vim's `10j`
+#[derive(Debug)]
+pub struct KeyParser {
+    count: Option<usize>,
+    keys: Vec<KeyEvent>,
+}
+
+pub enum ParsedKey {
+    Binding(Option<usize>, KeyBinding),
+    // The keys so far are the start of a binding, or a count
+    Pending,
+    Unbound,
+}
+
+impl KeyParser {
+    pub fn new() -> KeyParser {
+        KeyParser {
+            count: None,
+            keys: Vec::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.count = None;
+        self.keys.clear();
+    }
+
+    // A sequence that's bound and is also the start of a longer one always means the shorter one
+    pub fn push(&mut self, key: KeyEvent, bindings: &KeyBindings) -> ParsedKey {
+        if let Some(d) = count_digit(key) {
+            // Like vim, a count can't start with 0
+            if self.keys.is_empty() && (d != 0 || self.count.is_some()) {
+                self.count = Some(self.count.unwrap_or(0).saturating_mul(10).saturating_add(d));
+                return ParsedKey::Pending;
+            }
+        }
+
+        self.keys.push(key);
+        if let Some(&binding) = bindings.get(&self.keys) {
+            let count = self.count.take();
+            self.keys.clear();
+            ParsedKey::Binding(count, binding)
+        } else if bindings.keys().any(|k| k.starts_with(&self.keys)) {
+            ParsedKey::Pending
+        } else {
+            self.clear();
+            ParsedKey::Unbound
+        }
+    }
+}
+
+fn count_digit(key: KeyEvent) -> Option<usize> {
+    match key.code {
+        KeyCode::Char(c) if key.modifiers == KeyModifiers::NONE => c.to_digit(10).map(|d| d as usize),
+        _ => None,
+    }
 }
 
 fn char_event(c: char, mods: Option<KeyModifiers>) -> KeyEvent {
@@ -19,13 +335,116 @@ fn char_event(c: char, mods: Option<KeyModifiers>) -> KeyEvent {
 }
 
 pub fn default_keybindings(keys: &mut KeyBindings) {
-    keys.insert(char_event('q', None), KeyBinding::Quit);
-    keys.insert(char_event('k', None), KeyBinding::Up);
-    keys.insert(char_event('j', None), KeyBinding::Down);
+    keys.insert(vec![char_event('q', None)], KeyBinding::Quit);
+    keys.insert(vec![char_event('k', None)], KeyBinding::Up);
+    keys.insert(vec![char_event('j', None)], KeyBinding::Down);
     keys.insert(
-        KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+        vec![KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)],
         KeyBinding::Enter,
     );
-    keys.insert(char_event('K', Some(KeyModifiers::SHIFT)), KeyBinding::InfoPopup);
-    keys.insert(char_event(' ', None), KeyBinding::TogglePlayback);
+    keys.insert(vec![char_event('K', Some(KeyModifiers::SHIFT))], KeyBinding::InfoPopup);
+    keys.insert(vec![char_event(' ', None)], KeyBinding::TogglePlayback);
+    keys.insert(vec![char_event('h', None)], KeyBinding::Back);
+    keys.insert(
+        vec![KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE)],
+        KeyBinding::Back,
+    );
+    keys.insert(vec![char_event('o', Some(KeyModifiers::CONTROL))], KeyBinding::JumpBack);
+    keys.insert(vec![char_event('>', None)], KeyBinding::NextTrack);
+    keys.insert(vec![char_event('<', None)], KeyBinding::PreviousTrack);
+    keys.insert(vec![char_event(']', None)], KeyBinding::SeekForward);
+    keys.insert(vec![char_event('[', None)], KeyBinding::SeekBackward);
+    keys.insert(vec![char_event('r', None)], KeyBinding::Restart);
+    keys.insert(vec![char_event('d', None)], KeyBinding::Devices);
+    keys.insert(vec![char_event('+', None)], KeyBinding::VolumeUp);
+    keys.insert(vec![char_event('=', None)], KeyBinding::VolumeUp);
+    keys.insert(vec![char_event('-', None)], KeyBinding::VolumeDown);
+    keys.insert(vec![char_event('m', None)], KeyBinding::ToggleMute);
+    keys.insert(vec![char_event('s', None)], KeyBinding::ToggleShuffle);
+    keys.insert(vec![char_event('R', Some(KeyModifiers::SHIFT))], KeyBinding::CycleRepeat);
+    // alt-1 seeks to 10%, alt-2 to 20%, ..., alt-0 to the start
+    for n in 0..10 {
+        let c = std::char::from_digit(n, 10).unwrap();
+        keys.insert(vec![char_event(c, Some(KeyModifiers::ALT))], KeyBinding::SeekToPercent(n as u8 * 10));
+    }
+    keys.insert(vec![char_event('S', Some(KeyModifiers::SHIFT))], KeyBinding::Search);
+    keys.insert(vec![char_event(':', None)], KeyBinding::CommandLine);
+    keys.insert(vec![char_event('/', None)], KeyBinding::Find);
+    keys.insert(vec![char_event('n', None)], KeyBinding::FindNext);
+    keys.insert(vec![char_event('N', Some(KeyModifiers::SHIFT))], KeyBinding::FindPrev);
+    keys.insert(vec![char_event('g', None), char_event('g', None)], KeyBinding::Top);
+    keys.insert(vec![char_event('G', Some(KeyModifiers::SHIFT))], KeyBinding::Bottom);
+    keys.insert(vec![char_event('d', Some(KeyModifiers::CONTROL))], KeyBinding::HalfPageDown);
+    keys.insert(vec![char_event('u', Some(KeyModifiers::CONTROL))], KeyBinding::HalfPageUp);
+    keys.insert(vec![char_event('f', Some(KeyModifiers::CONTROL))], KeyBinding::PageDown);
+    keys.insert(vec![char_event('b', Some(KeyModifiers::CONTROL))], KeyBinding::PageUp);
+    keys.insert(vec![char_event('H', Some(KeyModifiers::SHIFT))], KeyBinding::ScreenTop);
+    keys.insert(vec![char_event('M', Some(KeyModifiers::SHIFT))], KeyBinding::ScreenMiddle);
+    keys.insert(vec![char_event('L', Some(KeyModifiers::SHIFT))], KeyBinding::ScreenBottom);
+    keys.insert(
+        vec![KeyEvent::new(KeyCode::Right, KeyModifiers::NONE)],
+        KeyBinding::NextGroup,
+    );
+    keys.insert(
+        vec![KeyEvent::new(KeyCode::Left, KeyModifiers::NONE)],
+        KeyBinding::PrevGroup,
+    );
+    // Vim's ctrl-i. Terminals send ctrl-i as tab, so tab is the only key there is for it
+    keys.insert(
+        vec![KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE)],
+        KeyBinding::JumpForward,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, mods: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, mods)
+    }
+
+    // What crossterm 0.17 reports on Unix for each of these
+    #[test]
+    fn parses_keys_the_way_crossterm_reports_them() {
+        let cases = [
+            ("j", key(KeyCode::Char('j'), KeyModifiers::NONE)),
+            ("G", key(KeyCode::Char('G'), KeyModifiers::SHIFT)),
+            ("<S-g>", key(KeyCode::Char('G'), KeyModifiers::SHIFT)),
+            ("<C-d>", key(KeyCode::Char('d'), KeyModifiers::CONTROL)),
+            ("<C-S-d>", key(KeyCode::Char('d'), KeyModifiers::CONTROL)),
+            ("<A-k>", key(KeyCode::Char('k'), KeyModifiers::ALT)),
+            ("<A-K>", key(KeyCode::Char('K'), KeyModifiers::ALT)),
+            ("<A-S-k>", key(KeyCode::Char('K'), KeyModifiers::ALT)),
+            ("<Tab>", key(KeyCode::Tab, KeyModifiers::NONE)),
+            ("<S-Tab>", key(KeyCode::BackTab, KeyModifiers::NONE)),
+            ("<CR>", key(KeyCode::Enter, KeyModifiers::NONE)),
+            ("<Space>", key(KeyCode::Char(' '), KeyModifiers::NONE)),
+        ];
+        for (notation, event) in cases.iter() {
+            assert_eq!(parse_keys(notation).unwrap(), vec![*event], "{}", notation);
+        }
+    }
+
+    #[test]
+    fn formatted_keys_parse_back_to_the_same_events() {
+        let events = [
+            key(KeyCode::Char('j'), KeyModifiers::NONE),
+            key(KeyCode::Char('G'), KeyModifiers::SHIFT),
+            key(KeyCode::Char('d'), KeyModifiers::CONTROL),
+            key(KeyCode::Char('K'), KeyModifiers::ALT),
+            key(KeyCode::Char('1'), KeyModifiers::ALT),
+            key(KeyCode::BackTab, KeyModifiers::NONE),
+            key(KeyCode::Tab, KeyModifiers::NONE),
+            key(KeyCode::Char('<'), KeyModifiers::NONE),
+            key(KeyCode::Char(' '), KeyModifiers::CONTROL),
+            key(KeyCode::F(5), KeyModifiers::NONE),
+        ];
+        for event in events.iter() {
+            let formatted = format_keys(&[*event]);
+            assert_eq!(parse_keys(&formatted).unwrap(), vec![*event], "{}", formatted);
+        }
+        assert_eq!(format_keys(&[key(KeyCode::BackTab, KeyModifiers::NONE)]), "<S-Tab>");
+        assert_eq!(format_keys(&[key(KeyCode::Char('K'), KeyModifiers::ALT)]), "<A-K>");
+    }
 }
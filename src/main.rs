@@ -1,154 +1,167 @@
 // use std::sync::{Arc, Mutex, RwLock};
 use std::sync::{Arc, Mutex};
-use tokio::sync::{mpsc, Mutex as TokioMutex, RwLock};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::time;
 
 #[macro_use]
 extern crate anyhow;
 
 use anyhow::Result;
 use crossterm::event;
-use lazy_static::lazy_static;
 
 mod api;
 mod app;
+mod cache;
+mod cli;
+mod command;
 mod config;
-pub mod data;
+mod control;
 mod keybindings;
+#[cfg(feature = "mpris")]
+mod mpris;
+mod network;
+mod scheduler;
+mod store;
+#[cfg(test)]
+mod tests;
 mod views;
 
-use api::{SpotifyApi, PageId};
+use api::{MusicBackend, OfflineBackend, SpotifyApi};
 use app::{App, Action, NetworkRequest};
+use cli::Args;
 use config::Config;
-use data::{add_playlist, add_playlist_summaries, PLAYLIST_SUMMARIES};
-use views::PlaylistScreen;
-
-lazy_static! {
-    static ref CHANNEL: (mpsc::Sender<NetworkRequest>, TokioMutex<mpsc::Receiver<NetworkRequest>>) = {
-        // TODO: why 10???
-        let (tx, rx) = mpsc::channel(10);
-        (tx, TokioMutex::new(rx))
-    };
-}
+use scheduler::Scheduler;
+use store::Store;
+use views::Output;
 
-pub fn send_request(r: NetworkRequest) {
-    tokio::spawn(async move {
-        CHANNEL.0.clone().send(r).await.unwrap();
-    });
-}
+// How often to ask Spotify what's playing, and how often to redraw the progress bar in between
+const PLAYBACK_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let config = Arc::new(Config::new()?);
-    let api = Arc::new(RwLock::new(SpotifyApi::new(Arc::clone(&config)).await.expect("auth failed...")));
+    let args = cli::parse_args(std::env::args().skip(1))?;
+    if let Args::Help = args {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+
+    let config = Config::new()?;
+    for warning in config.warnings.iter() {
+        eprintln!("warning: {}", warning);
+    }
 
-    let app = Arc::new(Mutex::new(App::new(Arc::clone(&config))));
+    let config = Arc::new(config);
+    let offline = matches!(args, Args::Tui { offline: true });
+    match args {
+        Args::CheckConfig => {
+            print!("{}", config.to_toml()?);
+            return Ok(());
+        }
+        Args::Login => return api::login(&config).await,
+        Args::Command(command) => return cli::run(command, config).await,
+        Args::Tui { .. } | Args::Help => {}
+    }
+
+    // Why Spotify couldn't be reached, if it couldn't
+    let mut unreachable = None;
+    let api: Box<dyn MusicBackend> = if offline {
+        Box::new(OfflineBackend::new(config.library_cache_dir.clone()))
+    } else {
+        match SpotifyApi::new(Arc::clone(&config)).await {
+            Ok(api) => {
+                api.keep_token_fresh();
+                Box::new(api)
+            }
+            Err(e) if network::is_unreachable(&e) => {
+                unreachable = Some(e);
+                Box::new(OfflineBackend::new(config.library_cache_dir.clone()))
+            }
+            Err(e) => return Err(e),
+        }
+    };
+    let api = Arc::new(RwLock::new(api));
+
+    let (store, requests) = Store::new();
+    let store = Arc::new(store);
+    let app = Arc::new(Mutex::new(App::new(Arc::clone(&config), Arc::clone(&store), Output::Terminal)));
+    // Before anything can change the store, so the app doesn't miss any of it
+    tokio::spawn(app::watch_store(store.subscribe(), Arc::clone(&app)));
+
+    // Done before the interface starts so a problem with it can still be printed
+    if let Some(ref path) = config.socket_path {
+        match control::bind(path) {
+            Ok((listener, socket)) => {
+                app.lock().unwrap().set_socket(socket);
+                tokio::spawn(control::serve(listener, Arc::clone(&app), Arc::clone(&store)));
+            }
+            Err(e) => eprintln!("warning: no control socket: {}", e),
+        }
+    }
+    #[cfg(feature = "mpris")]
+    {
+        if let Err(e) = mpris::start(Arc::clone(&store)) {
+            eprintln!("warning: no MPRIS: {}", e);
+        }
+    }
+
+    app::install_panic_hook();
     app.lock().unwrap().start()?;
+    if let Some(ref e) = unreachable {
+        network::report_offline(&app, e);
+    }
+    // Shared so the first fetches wait their turn like everything after
+    let scheduler = Arc::new(Scheduler::new());
+    let scheduler_init = Arc::clone(&scheduler);
     let app_init = Arc::clone(&app);
     let api_init = api.clone();
+    let store_init = Arc::clone(&store);
+    let config_init = Arc::clone(&config);
     tokio::spawn(async move {
-        init(app_init, api_init).await.unwrap();
+        let init = network::init(scheduler_init, Arc::clone(&app_init), api_init, store_init, config_init);
+        if let Err(e) = init.await {
+            network::report_error(&app_init, "loading playlists", e);
+        }
     });
 
-    let app_handler = Arc::clone(&app);
+    tokio::spawn(network::handle_requests(
+        requests,
+        scheduler,
+        Arc::clone(&api),
+        Arc::clone(&app),
+        Arc::clone(&store),
+        Arc::clone(&config),
+    ));
+
+    store.send_request(NetworkRequest::GetDevices);
+
+    let store_poller = Arc::clone(&store);
     tokio::spawn(async move {
-        let mut rx = CHANNEL.1.lock().await;
-        while let Some(r) = rx.recv().await {
-            match r {
-                NetworkRequest::LoadNextPage(page_id) => {
-                    let api = Arc::clone(&api);
-                    let app = Arc::clone(&app_handler);
-                    tokio::spawn(async move {
-                        match page_id {
-                            PageId::Playlists => {
-                                let index = {
-                                    let ps = PLAYLIST_SUMMARIES.lock().unwrap();
-                                    ps.next_page().map(|np| np.index)
-                                };
-                                if let Some(index) = index {
-                                    let p = api.read().await.get_playlists(index).await.unwrap();
-                                    add_playlist_summaries(p);
-
-                                    let mut app = app.lock().unwrap();
-                                    app.handle_action(Action::PlaylistsUpdated).unwrap();
-                                }
-                            }
-                            PageId::Playlist(_) => {}
-                        }
-                    });
-                }
-                NetworkRequest::LoadPlaylist(id) => {
-                    let api = Arc::clone(&api);
-                    let app = Arc::clone(&app_handler);
-                    tokio::spawn(async move {
-                        let p = api.read().await.get_playlist(&id).await.unwrap();
-                        let id = p.id().to_owned();
-                        add_playlist(p);
-
-                        let screen = Box::new(PlaylistScreen::new(id));
-
-                        // TODO: just notifiy don't add a screen
-                        app.lock().unwrap().add_screen(screen).unwrap();
-                    });
-                }
-                NetworkRequest::GetDevices => {
-                    let api1 = Arc::clone(&api);
-                    let api2 = Arc::clone(&api);
-                    tokio::spawn(async move {
-                        let device_id = api1.read().await
-                            .get_devices()
-                            .await
-                            .unwrap()
-                            .into_iter()
-                            .next()
-                            .map(|d| d.id);
-
-                        api1.write().await.set_device_id(device_id);
-                    });
-                    tokio::spawn(async move {
-                        let is_playing = api2.read().await
-                            .playback_status()
-                            .await
-                            .unwrap()
-                            .map(|ctx| ctx.is_playing)
-                            .unwrap_or(false);
-
-                        api2.write().await.set_playing(is_playing);
-                    });
-                }
-                NetworkRequest::TogglePlayback => {
-                    let api = Arc::clone(&api);
-                    tokio::spawn(async move {
-                        match api.write().await.toggle_playback().await {
-                            _ => {}
-                        };
-                    });
-                }
-                NetworkRequest::PlayUri(uri) => {
-                    let api = Arc::clone(&api);
-                    tokio::spawn(async move {
-                        api.write().await.play_from_uri(uri).await.unwrap();
-                    });
-                }
-            }
+        let mut interval = time::interval(PLAYBACK_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            store_poller.send_request(NetworkRequest::GetPlaybackStatus);
         }
     });
 
-    send_request(NetworkRequest::GetDevices);
+    let app_ticker = Arc::clone(&app);
+    tokio::spawn(async move {
+        let mut interval = time::interval(TICK_INTERVAL);
+        loop {
+            interval.tick().await;
+            app::update(&app_ticker, |app| app.handle_action(Action::Tick));
+        }
+    });
 
     loop {
         if let Ok(event::Event::Key(e)) = event::read() {
-            // TODO: What to do when in a text box or something???
-            // Probably send not only the KeyBinding but also the raw key entered, ya that...
-            if let Some(&key) = config.keybindings.get(&e) {
-                app.lock().unwrap().handle_key(key)?;
+            let mut app = app.lock().unwrap();
+            // Put the terminal back before the error gets printed
+            if let Err(e) = app.handle_event(e) {
+                app.stop()?;
+                return Err(e);
             }
         }
     }
 }
-
-pub async fn init(app: Arc<Mutex<App>>, api: Arc<RwLock<SpotifyApi>>) -> Result<()> {
-    let p = api.read().await.get_playlists(0).await.unwrap();
-    add_playlist_summaries(p);
-    app.lock().unwrap().handle_action(Action::PlaylistsUpdated)?;
-    Ok(())
-}
@@ -0,0 +1,555 @@
+// The MPRIS D-Bus interface, so media keys and desktop widgets can see and control spotivi.
+// Calls from D-Bus become requests on the store like keys do, and the properties come from it.
+
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::io;
+use std::mem::ManuallyDrop;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use rspotify::senum::RepeatState;
+use zbus::{dbus_interface, fdo, Connection, ObjectServer};
+use zvariant::{ObjectPath, Value};
+
+use crate::api::PlaybackState;
+use crate::app::NetworkRequest;
+use crate::command::{self, UriKind};
+use crate::store::{Events, Store, StoreEvent};
+
+// Each instance adds its pid, like the spec asks, so a second spotivi doesn't take over the first
+const BUS_NAME_PREFIX: &str = "org.mpris.MediaPlayer2.spotivi";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+// Spotify's api doesn't give the track id with the rest of the playback state,
+// so there's one path for whatever is playing
+const TRACK_PATH: &str = "/org/spotivi/CurrentTrack";
+const NO_TRACK_PATH: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+// More than this off from where playback should be by now counts as a seek. The position is
+// only polled every few seconds, so it drifts a little anyway
+const SEEK_TOLERANCE_MS: i64 = 2000;
+// How long to wait before checking for calls again when there weren't any
+const CALL_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+// Where playback was at the last update, to tell a seek from just playing on
+struct Position {
+    // The title and duration, since the api doesn't give an id
+    track: (Option<String>, u32),
+    progress_ms: u32,
+    playing: bool,
+    at: Instant,
+}
+
+impl Position {
+    fn new(p: &PlaybackState) -> Position {
+        Position {
+            track: (p.title().map(|t| t.to_owned()), p.duration_ms()),
+            progress_ms: p.progress_ms(),
+            playing: p.is_playing(),
+            at: Instant::now(),
+        }
+    }
+
+    // Whether `now` is somewhere this couldn't have got to by playing
+    fn jumped_to(&self, now: &Position) -> bool {
+        let elapsed = if self.playing { self.at.elapsed().as_millis() as i64 } else { 0 };
+        let expected = self.progress_ms as i64 + elapsed;
+        self.track == now.track && (now.progress_ms as i64 - expected).abs() > SEEK_TOLERANCE_MS
+    }
+}
+
+fn bus_name() -> String {
+    format!("{}.instance{}", BUS_NAME_PREFIX, std::process::id())
+}
+
+// Takes the session bus and answers calls on it from another thread
+pub fn start(store: Arc<Store>) -> Result<()> {
+    start_with_connection(Connection::new_session()?, store)
+}
+
+pub fn start_with_connection(connection: Connection, store: Arc<Store>) -> Result<()> {
+    let reply = fdo::DBusProxy::new(&connection)?
+        .request_name(&bus_name(), fdo::RequestNameFlags::DoNotQueue.into())?;
+    if !matches!(reply, fdo::RequestNameReply::PrimaryOwner) {
+        bail!("{} is already taken", bus_name());
+    }
+
+    // Waiting for a call holds on to the connection, so no signals could go out until one came.
+    // Without blocking it's let go of between checks
+    set_nonblocking(&connection)?;
+
+    // Calls are checked for on a thread of their own. The server can't be sent to it, so it's
+    // made there and only whether that worked comes back
+    let (ready, started) = mpsc::channel();
+    let server_connection = connection.clone();
+    let player = Player { store: Arc::clone(&store) };
+    thread::spawn(move || {
+        let mut server = ObjectServer::new(&server_connection);
+        let added = add_interfaces(&mut server, player);
+        let failed = added.is_err();
+        ready.send(added).ok();
+        if failed {
+            return;
+        }
+        loop {
+            match server.try_handle_next() {
+                Err(zbus::Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(CALL_POLL_INTERVAL),
+                // The connection is gone, so nothing more will come
+                Err(zbus::Error::Io(_)) => break,
+                // A bad call from one client shouldn't stop the others
+                _ => {}
+            }
+        }
+    });
+    started.recv()??;
+
+    let signals = Signals {
+        connection,
+        last_properties: None,
+        last_position: None,
+    };
+    tokio::spawn(signals.watch(store.subscribe(), store));
+
+    Ok(())
+}
+
+fn set_nonblocking(connection: &Connection) -> Result<()> {
+    // Borrowed from the connection, so it isn't closed when this goes away
+    let socket = ManuallyDrop::new(unsafe { UnixStream::from_raw_fd(connection.as_raw_fd()) });
+    socket.set_nonblocking(true)?;
+    Ok(())
+}
+
+fn add_interfaces(server: &mut ObjectServer, player: Player) -> Result<()> {
+    server.at(&OBJECT_PATH.try_into()?, Root)?;
+    server.at(&OBJECT_PATH.try_into()?, player)?;
+    Ok(())
+}
+
+// Sends the signals for what changed in playback, for as long as spotivi runs
+struct Signals {
+    connection: Connection,
+    // The properties last sent in PropertiesChanged, to only send them when something changed
+    last_properties: Option<HashMap<&'static str, Value<'static>>>,
+    last_position: Option<Position>,
+}
+
+impl Signals {
+    async fn watch(mut self, mut events: Events, store: Arc<Store>) {
+        while let Some(event) = events.recv().await {
+            if let StoreEvent::PlaybackChanged = event {
+                self.playback_updated(&store);
+            }
+        }
+    }
+
+    fn playback_updated(&mut self, store: &Store) {
+        let properties = {
+            let playback = store.playback();
+            let p = playback.as_ref();
+
+            let position = p.map(Position::new);
+            if let (Some(last), Some(now)) = (self.last_position.as_ref(), position.as_ref()) {
+                if last.jumped_to(now) {
+                    // In microseconds
+                    let position_us = now.progress_ms as i64 * 1000;
+                    self.connection.emit_signal(None, OBJECT_PATH, PLAYER_INTERFACE, "Seeked", &position_us).ok();
+                }
+            }
+            self.last_position = position;
+
+            let mut properties = HashMap::new();
+            properties.insert("PlaybackStatus", Value::from(playback_status(p)));
+            properties.insert("Metadata", Value::from(metadata(p)));
+            properties.insert("Volume", Value::from(volume(p)));
+            properties.insert("Shuffle", Value::from(p.map(|p| p.shuffle()).unwrap_or(false)));
+            properties.insert("LoopStatus", Value::from(loop_status(p)));
+            properties
+        };
+
+        if self.last_properties.as_ref() == Some(&properties) {
+            return;
+        }
+        let invalidated: Vec<&str> = Vec::new();
+        self.connection.emit_signal(
+            None,
+            OBJECT_PATH,
+            "org.freedesktop.DBus.Properties",
+            "PropertiesChanged",
+            &(PLAYER_INTERFACE, &properties, invalidated),
+        ).ok();
+        self.last_properties = Some(properties);
+    }
+}
+
+fn playback_status(p: Option<&PlaybackState>) -> String {
+    match p {
+        Some(p) if p.is_playing() => "Playing",
+        Some(_) => "Paused",
+        None => "Stopped",
+    }.to_owned()
+}
+
+fn metadata(p: Option<&PlaybackState>) -> HashMap<String, Value<'static>> {
+    let mut metadata = HashMap::new();
+    let p = match p {
+        Some(p) => p,
+        None => {
+            let path = ObjectPath::try_from(NO_TRACK_PATH).unwrap();
+            metadata.insert("mpris:trackid".to_owned(), Value::from(path));
+            return metadata;
+        }
+    };
+
+    let path = ObjectPath::try_from(TRACK_PATH).unwrap();
+    metadata.insert("mpris:trackid".to_owned(), Value::from(path));
+    // In microseconds
+    metadata.insert("mpris:length".to_owned(), Value::from(p.duration_ms() as i64 * 1000));
+    if let Some(title) = p.title() {
+        metadata.insert("xesam:title".to_owned(), Value::from(title.to_owned()));
+    }
+    if let Some(artists) = p.artists() {
+        let artists: Vec<String> = artists.split(", ").map(|a| a.to_owned()).collect();
+        metadata.insert("xesam:artist".to_owned(), Value::from(artists));
+    }
+    metadata
+}
+
+fn volume(p: Option<&PlaybackState>) -> f64 {
+    p.map(|p| p.volume_percent() as f64 / 100.0).unwrap_or(0.0)
+}
+
+fn loop_status(p: Option<&PlaybackState>) -> String {
+    match p.map(|p| p.repeat()) {
+        Some(RepeatState::Track) => "Track",
+        Some(RepeatState::Context) => "Playlist",
+        _ => "None",
+    }.to_owned()
+}
+
+struct Root;
+
+#[dbus_interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[dbus_interface(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[dbus_interface(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[dbus_interface(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[dbus_interface(property)]
+    fn identity(&self) -> String {
+        "spotivi".to_owned()
+    }
+
+    #[dbus_interface(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec!["spotify".to_owned()]
+    }
+
+    #[dbus_interface(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+struct Player {
+    store: Arc<Store>,
+}
+
+#[dbus_interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn play_pause(&self) {
+        self.store.send_request(NetworkRequest::TogglePlayback);
+    }
+
+    fn play(&self) {
+        self.store.send_request(NetworkRequest::Play);
+    }
+
+    fn pause(&self) {
+        self.store.send_request(NetworkRequest::Pause);
+    }
+
+    // Spotify doesn't have stop
+    fn stop(&self) {
+        self.store.send_request(NetworkRequest::Pause);
+    }
+
+    fn next(&self) {
+        self.store.send_request(NetworkRequest::NextTrack);
+    }
+
+    fn previous(&self) {
+        self.store.send_request(NetworkRequest::PreviousTrack);
+    }
+
+    // MPRIS times are in microseconds
+    fn seek(&self, offset: i64) {
+        self.store.send_request(NetworkRequest::SeekBy(offset / 1000));
+    }
+
+    // Like the spec says, a position meant for another track is ignored, since it's likely
+    // from before the track changed
+    fn set_position(&self, track_id: ObjectPath, position: i64) {
+        if track_id.as_str() != TRACK_PATH {
+            return;
+        }
+        self.store.send_request(NetworkRequest::SeekTo((std::cmp::max(position, 0) / 1000) as u32));
+    }
+
+    fn open_uri(&self, uri: &str) -> fdo::Result<()> {
+        match command::parse_uri(uri) {
+            Some((UriKind::Track, id)) => self.store.send_request(NetworkRequest::PlayUri(command::uri(UriKind::Track, &id))),
+            Some((kind, id)) => self.store.send_request(NetworkRequest::PlayContext(command::uri(kind, &id), None)),
+            None => return Err(fdo::Error::InvalidArgs(format!("Not a Spotify uri: {}", uri))),
+        }
+        Ok(())
+    }
+
+    #[dbus_interface(property)]
+    fn playback_status(&self) -> String {
+        playback_status(self.store.playback().as_ref())
+    }
+
+    #[dbus_interface(property)]
+    fn metadata(&self) -> HashMap<String, Value<'static>> {
+        metadata(self.store.playback().as_ref())
+    }
+
+    #[dbus_interface(property)]
+    fn volume(&self) -> f64 {
+        volume(self.store.playback().as_ref())
+    }
+
+    #[dbus_interface(property)]
+    fn set_volume(&mut self, volume: f64) {
+        let percent = (volume.clamp(0.0, 1.0) * 100.0).round() as u8;
+        self.store.send_request(NetworkRequest::SetVolume(percent));
+    }
+
+    #[dbus_interface(property)]
+    fn position(&self) -> i64 {
+        self.store.playback()
+            .as_ref()
+            .map(|p| p.progress_ms() as i64 * 1000)
+            .unwrap_or(0)
+    }
+
+    #[dbus_interface(property)]
+    fn shuffle(&self) -> bool {
+        self.store.playback().as_ref().map(|p| p.shuffle()).unwrap_or(false)
+    }
+
+    #[dbus_interface(property)]
+    fn loop_status(&self) -> String {
+        loop_status(self.store.playback().as_ref())
+    }
+
+    #[dbus_interface(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[dbus_interface(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[dbus_interface(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[dbus_interface(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn can_seek(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+
+    use tokio::task;
+    use zvariant::OwnedValue;
+
+    use super::*;
+    use crate::api::MusicBackend;
+    use crate::tests::{library, road_trip_playing};
+
+    // A session bus of our own, so the tests don't need a desktop
+    struct PrivateBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl PrivateBus {
+        // None if dbus-daemon isn't installed
+        fn start() -> Option<PrivateBus> {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address=1"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.as_mut()?).read_line(&mut address).ok()?;
+            Some(PrivateBus {
+                daemon,
+                address: address.trim().to_owned(),
+            })
+        }
+
+        fn connect(&self) -> Connection {
+            Connection::new_for_address(&self.address, true).unwrap()
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            self.daemon.kill().ok();
+            self.daemon.wait().ok();
+        }
+    }
+
+    fn get_property(client: &Connection, name: &str) -> zvariant::OwnedValue {
+        fdo::PropertiesProxy::new_for(client, &bus_name(), OBJECT_PATH)
+            .unwrap()
+            .get(PLAYER_INTERFACE, name)
+            .unwrap()
+    }
+
+    fn call(client: &Connection, method: &str, body: &(impl serde::Serialize + zvariant::Type)) {
+        client.call_method(Some(bus_name().as_str()), OBJECT_PATH, Some(PLAYER_INTERFACE), method, body)
+            .unwrap();
+    }
+
+    // Waits for the signal called `member`, leaving anything else the bus sends
+    fn next_signal(client: &Connection, member: &str) -> zbus::Message {
+        client.receive_specific(|m| {
+            let header = m.header()?;
+            Ok(header.message_type()? == zbus::MessageType::Signal && header.member()? == Some(member))
+        }).unwrap()
+    }
+
+    // Everything shares one test since there's only one server per process
+    #[tokio::test(threaded_scheduler)]
+    async fn player_over_private_bus() {
+        let bus = match PrivateBus::start() {
+            Some(bus) => bus,
+            None => {
+                eprintln!("Skipping, dbus-daemon isn't installed");
+                return;
+            }
+        };
+        let (store, mut requests) = Store::new();
+        let store = Arc::new(store);
+        start_with_connection(bus.connect(), Arc::clone(&store)).unwrap();
+        let client = bus.connect();
+        fdo::DBusProxy::new(&client).unwrap()
+            .add_match(&format!("type='signal',path='{}'", OBJECT_PATH))
+            .unwrap();
+
+        // The name is this instance's, so another one can't take it
+        let (other, _) = Store::new();
+        assert!(start_with_connection(bus.connect(), Arc::new(other)).is_err());
+
+        // Nothing has been fetched yet
+        let status: String = get_property(&client, "PlaybackStatus").try_into().unwrap();
+        assert_eq!(status, "Stopped");
+
+        call(&client, "PlayPause", &());
+        match requests.recv().await {
+            Some(NetworkRequest::TogglePlayback) => {}
+            r => panic!("expected TogglePlayback, got {:?}", r),
+        }
+
+        call(&client, "Seek", &(-5_000_000i64));
+        match requests.recv().await {
+            Some(NetworkRequest::SeekBy(-5000)) => {}
+            r => panic!("expected SeekBy(-5000), got {:?}", r),
+        }
+
+        let playing = road_trip_playing().await;
+        let volume_percent = playing.volume_percent();
+        store.set_playback(Some(playing));
+        let changed = task::block_in_place(|| next_signal(&client, "PropertiesChanged"));
+        let (interface, properties, _): (String, HashMap<String, OwnedValue>, Vec<String>) = changed.body().unwrap();
+        assert_eq!(interface, PLAYER_INTERFACE);
+        let status: String = properties["PlaybackStatus"].clone().try_into().unwrap();
+        assert_eq!(status, "Playing");
+
+        let metadata: HashMap<String, OwnedValue> = get_property(&client, "Metadata").try_into().unwrap();
+        let title: String = metadata["xesam:title"].clone().try_into().unwrap();
+        assert_eq!(title, "Highway Song");
+        let artists: Vec<String> = metadata["xesam:artist"].clone().try_into().unwrap();
+        assert_eq!(artists, vec!["The Drivers"]);
+        let volume: f64 = get_property(&client, "Volume").try_into().unwrap();
+        assert_eq!(volume, volume_percent as f64 / 100.0);
+
+        // A minute in, which playing for a moment couldn't have got to
+        store.set_playback(Some(road_trip_at(60_000).await));
+        let seeked = task::block_in_place(|| next_signal(&client, "Seeked"));
+        assert_eq!(seeked.body::<i64>().unwrap(), 60_000_000);
+
+        // Only a position for the track that's playing is used
+        let other_track = ObjectPath::try_from("/org/spotivi/OtherTrack").unwrap();
+        call(&client, "SetPosition", &(other_track, 1_000_000i64));
+        let this_track = ObjectPath::try_from(TRACK_PATH).unwrap();
+        call(&client, "SetPosition", &(this_track, 2_000_000i64));
+        match requests.recv().await {
+            Some(NetworkRequest::SeekTo(2000)) => {}
+            r => panic!("expected SeekTo(2000), got {:?}", r),
+        }
+    }
+
+    async fn road_trip_at(position_ms: u32) -> PlaybackState {
+        let mut backend = library(&[]);
+        backend.play_context("spotify:playlist:road".to_owned(), None).await.unwrap();
+        backend.seek(position_ms).await.unwrap();
+        backend.playback_status().await.unwrap().unwrap()
+    }
+}
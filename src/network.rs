@@ -0,0 +1,366 @@
+// Carries out the requests from Store::send_request against the backend, and puts what comes
+// back in the store
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use tokio::sync::RwLock;
+
+use crate::api::{
+    next_repeat_state,
+    MusicBackend,
+    OfflineBackend,
+    PageId,
+    Playlist,
+    SearchKind,
+    TransientError,
+    PAGE_SIZE,
+};
+use crate::app::{self, Action, App, NetworkRequest};
+use crate::cache;
+use crate::config::Config;
+use crate::scheduler::Scheduler;
+use crate::store::{Requests, Store};
+
+pub type Backend = Arc<RwLock<Box<dyn MusicBackend>>>;
+
+// Each request gets its own task, so a slow one doesn't hold up the rest.
+// The scheduler decides when each one actually goes out
+pub async fn handle_requests(
+    mut requests: Requests,
+    scheduler: Arc<Scheduler>,
+    api: Backend,
+    app: Arc<Mutex<App>>,
+    store: Arc<Store>,
+    config: Arc<Config>,
+) {
+    while let Some(r) = requests.recv().await {
+        tokio::spawn(handle_request(
+            r,
+            Arc::clone(&scheduler),
+            Arc::clone(&api),
+            Arc::clone(&app),
+            Arc::clone(&store),
+            Arc::clone(&config),
+        ));
+    }
+}
+
+// Anything that goes wrong is shown to the user rather than taking down the task
+pub async fn handle_request(
+    r: NetworkRequest,
+    scheduler: Arc<Scheduler>,
+    api: Backend,
+    app: Arc<Mutex<App>>,
+    store: Arc<Store>,
+    config: Arc<Config>,
+) {
+    let doing = r.description();
+    let opening = match r {
+        NetworkRequest::LoadPlaylist(ref id) => Some(id.clone()),
+        _ => None,
+    };
+    let result = match r {
+        NetworkRequest::Search(query) => search(query, scheduler, api, Arc::clone(&store)).await,
+        r => scheduler.run(r, |r| {
+            run_request(r, Arc::clone(&api), Arc::clone(&store), Arc::clone(&config))
+        }).await,
+    };
+    if let Err(e) = result {
+        // So it doesn't open out of nowhere if it gets loaded some other way later
+        if let Some(id) = opening {
+            app.lock().unwrap().cancel_opening(&id);
+        }
+        report_error(&app, doing, e);
+    }
+}
+
+pub fn report_error(app: &Mutex<App>, doing: &str, e: anyhow::Error) {
+    let message = format!("Error {}: {}", doing, e);
+    app::update(app, |app| app.handle_action(Action::Error(message)));
+}
+
+// Whether Spotify couldn't be reached at all after `e`, so spotivi can carry on offline. Spotify
+// answering with an error, even one that might pass like being asked to slow down, isn't that
+pub fn is_unreachable(e: &anyhow::Error) -> bool {
+    matches!(e.downcast_ref::<TransientError>(), Some(TransientError::Network(_)))
+}
+
+// Nothing tries to go back online after this, so it says how to
+pub fn report_offline(app: &Mutex<App>, e: &anyhow::Error) {
+    let message = format!("Offline, only saved playlists can be browsed until spotivi is restarted: {}", e);
+    app::update(app, |app| app.handle_action(Action::Notice(message)));
+}
+
+async fn run_request(r: NetworkRequest, api: Backend, store: Arc<Store>, config: Arc<Config>) -> Result<()> {
+    match r {
+        NetworkRequest::LoadNextPage(page_id) => {
+            match page_id {
+                PageId::Playlists => {
+                    let index = {
+                        let ps = store.playlist_summaries();
+                        ps.next_page().map(|np| np.index)
+                    };
+                    if let Some(index) = index {
+                        let p = api.read().await.get_playlists(index).await?;
+                        store.add_playlist_summaries(index, p);
+                        save_summaries(&store, &config);
+                    }
+                }
+                PageId::Playlist(id) => {
+                    let next = {
+                        let playlists = store.playlists();
+                        playlists.get(&id)
+                            .filter(|p| p.tracks().next_page().is_some())
+                            .map(|p| (p.owner_id().to_owned(), p.items().len()))
+                    };
+                    if let Some((owner_id, offset)) = next {
+                        let tracks = api.read().await
+                            .get_playlist_tracks(&owner_id, &id, offset as u32)
+                            .await?;
+                        store.add_playlist_tracks(&id, offset, tracks);
+                        save_playlist(&store, &config, &id);
+                    }
+                }
+                // All of the devices come back in one request
+                PageId::Devices => {}
+                PageId::Search { query, kind } => {
+                    let offset = {
+                        let results = store.search_results();
+                        Some(results.len(kind))
+                            .filter(|_| results.query() == query && results.has_next_page(kind))
+                    };
+                    if let Some(offset) = offset {
+                        let page = api.read().await
+                            .search(&query, kind, offset as u32)
+                            .await?;
+                        store.add_search_page(&query, offset, page);
+                    }
+                }
+            }
+        }
+        NetworkRequest::LoadPlaylist(id) => {
+            let p = match cached_playlist(&store, &config, &id) {
+                Some(p) => p,
+                None => {
+                    let p = api.read().await.get_playlist(&id).await?;
+                    cache::save_playlist(&config.library_cache_dir, &p).ok();
+                    p
+                }
+            };
+            store.add_playlist(p);
+        }
+        NetworkRequest::GetDevices => {
+            let devices = api.read().await.get_devices().await?;
+            api.write().await.choose_device(&devices, config.saved_device_id());
+            store.set_devices(devices);
+        }
+        NetworkRequest::TransferPlayback(id, play) => {
+            api.write().await.transfer_playback(id.clone(), play).await?;
+            config.save_device_id(&id)?;
+
+            store.send_request(NetworkRequest::GetDevices);
+            store.send_request(NetworkRequest::GetPlaybackStatus);
+        }
+        NetworkRequest::GetPlaybackStatus => {
+            let playback = api.read().await.playback_status().await?;
+
+            let is_playing = playback.as_ref().map(|p| p.is_playing()).unwrap_or(false);
+            api.write().await.set_playing(is_playing);
+            store.set_playback(playback);
+        }
+        // These go to search() instead, see handle_request
+        NetworkRequest::Search(_) => unreachable!(),
+        NetworkRequest::TogglePlayback => {
+            api.write().await.toggle_playback().await?;
+            store.send_request(NetworkRequest::GetPlaybackStatus);
+        }
+        NetworkRequest::Play => {
+            api.write().await.resume_playback().await?;
+            store.send_request(NetworkRequest::GetPlaybackStatus);
+        }
+        NetworkRequest::Pause => {
+            api.write().await.pause_playback().await?;
+            store.send_request(NetworkRequest::GetPlaybackStatus);
+        }
+        NetworkRequest::PlayUri(uri) => {
+            api.write().await.play_from_uri(uri).await?;
+            store.send_request(NetworkRequest::GetPlaybackStatus);
+        }
+        NetworkRequest::PlayContext(uri, position) => {
+            api.write().await.play_context(uri, position).await?;
+            store.send_request(NetworkRequest::GetPlaybackStatus);
+        }
+        NetworkRequest::SetVolume(volume) => {
+            api.read().await.set_volume(volume).await?;
+        }
+        NetworkRequest::ToggleShuffle => {
+            let shuffle = store.playback().as_ref().map(|p| !p.shuffle());
+            if let Some(shuffle) = shuffle {
+                api.read().await.set_shuffle(shuffle).await?;
+
+                store.update_playback(|p| p.set_shuffle(shuffle));
+            }
+        }
+        NetworkRequest::CycleRepeat => {
+            let repeat = store.playback().as_ref().map(|p| next_repeat_state(p.repeat()));
+            if let Some(repeat) = repeat {
+                api.read().await.set_repeat(repeat).await?;
+
+                store.update_playback(|p| p.set_repeat(repeat));
+            }
+        }
+        NetworkRequest::NextTrack | NetworkRequest::PreviousTrack => {
+            {
+                let api = api.read().await;
+                match r {
+                    NetworkRequest::NextTrack => api.next_track().await?,
+                    _ => api.previous_track().await?,
+                }
+            }
+            store.send_request(NetworkRequest::GetPlaybackStatus);
+        }
+        NetworkRequest::SeekForward
+        | NetworkRequest::SeekBackward
+        | NetworkRequest::SeekToPercent(_)
+        | NetworkRequest::Restart
+        | NetworkRequest::SeekBy(_)
+        | NetworkRequest::SeekTo(_) => {
+            let seek_ms = config.seek_seconds as i64 * 1000;
+            let position = {
+                let playback = store.playback();
+                playback.as_ref().map(|p| match r {
+                    NetworkRequest::SeekForward => p.seek_position(seek_ms),
+                    NetworkRequest::SeekBackward => p.seek_position(-seek_ms),
+                    NetworkRequest::SeekToPercent(percent) => p.percent_position(percent),
+                    NetworkRequest::Restart => 0,
+                    NetworkRequest::SeekBy(offset_ms) => p.seek_position(offset_ms),
+                    NetworkRequest::SeekTo(position_ms) => std::cmp::min(position_ms, p.duration_ms()),
+                    _ => unreachable!(),
+                })
+            };
+            if let Some(position) = position {
+                api.read().await.seek(position).await?;
+
+                store.update_playback(|p| p.set_progress_ms(position));
+            }
+        }
+    }
+    Ok(())
+}
+
+// All the kinds at once, the screen shows each one as it comes in. Each kind is a read of its
+// own, so it takes its own place in the scheduler and is tried again without the others
+async fn search(query: String, scheduler: Arc<Scheduler>, api: Backend, store: Arc<Store>) -> Result<()> {
+    let searches: Vec<_> = SearchKind::ALL.iter().map(|&kind| {
+        let scheduler = Arc::clone(&scheduler);
+        let api = Arc::clone(&api);
+        let store = Arc::clone(&store);
+        let query = query.clone();
+        tokio::spawn(async move {
+            let (api, query) = (&api, &query);
+            let page = scheduler.read(|| async move { api.read().await.search(query, kind, 0).await }).await?;
+            store.add_search_page(query, 0, page);
+            Ok::<(), anyhow::Error>(())
+        })
+    }).collect();
+    // Each kind gets to finish, and all the ones that failed are reported
+    let mut failed = Vec::new();
+    for (&kind, search) in SearchKind::ALL.iter().zip(searches) {
+        if let Err(e) = search.await.map_err(|e| anyhow!(e)).and_then(|r| r) {
+            failed.push((kind, e));
+        }
+    }
+    // Failing the same way each time, like when Spotify can't be reached, is kept as one error
+    let same = failed.windows(2).all(|w| w[0].1.to_string() == w[1].1.to_string());
+    match failed.len() {
+        0 => Ok(()),
+        _ if same => Err(failed.remove(0).1),
+        _ => {
+            let messages: Vec<String> = failed.iter()
+                .map(|(kind, e)| format!("{}: {}", kind.name(), e))
+                .collect();
+            bail!("{}", messages.join(", "))
+        }
+    }
+}
+
+// Shows the playlists saved last time right away, then checks them against Spotify.
+// Only the playlists that changed since are fetched again
+pub async fn init(
+    scheduler: Arc<Scheduler>,
+    app: Arc<Mutex<App>>,
+    api: Backend,
+    store: Arc<Store>,
+    config: Arc<Config>,
+) -> Result<()> {
+    let mut pages = 1;
+    let cached = cache::load_summaries(&config.library_cache_dir);
+    let showing_cached = cached.is_some();
+    if let Some(p) = cached {
+        pages = std::cmp::max((p.len() as u32).div_ceil(PAGE_SIZE), 1);
+        store.set_playlist_summaries(p);
+    }
+
+    // As many pages as were loaded last time, so the list doesn't shrink under the cursor.
+    // Each is tried again like any other read before giving up on Spotify
+    let api = &api;
+    let first = scheduler.read(|| async move { api.read().await.get_playlists(0).await }).await;
+    let mut fresh = match first {
+        Ok(p) => p,
+        // What was saved is already showing, so carry on with just that
+        Err(e) if showing_cached && is_unreachable(&e) => {
+            *api.write().await = Box::new(OfflineBackend::new(config.library_cache_dir.clone()));
+            report_offline(&app, &e);
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    for page in 1..pages {
+        if fresh.next_page().is_none() {
+            break;
+        }
+        let next = scheduler.read(|| async move { api.read().await.get_playlists(page).await }).await?;
+        fresh.add_page(next);
+    }
+
+    let changed: Vec<String> = {
+        let playlists = store.playlists();
+        fresh.items().iter()
+            .filter(|s| playlists.get(s.id()).map(|p| p.snapshot_id() != s.snapshot_id()).unwrap_or(false))
+            .map(|s| s.id().to_owned())
+            .collect()
+    };
+    store.set_playlist_summaries(fresh);
+    save_summaries(&store, &config);
+
+    // Ones already opened from the cache while this was going on
+    for id in changed {
+        let id = &id;
+        let p = scheduler.read(|| async move { api.read().await.get_playlist(id).await }).await?;
+        cache::save_playlist(&config.library_cache_dir, &p).ok();
+        store.add_playlist(p);
+    }
+    Ok(())
+}
+
+// The saved copy, if it's the same version the playlist's summary has
+fn cached_playlist(store: &Store, config: &Config, id: &str) -> Option<Playlist> {
+    let snapshot_id = store.playlist_summaries()
+        .items()
+        .iter()
+        .find(|s| s.id() == id)
+        .map(|s| s.snapshot_id().to_owned())?;
+    cache::load_playlist(&config.library_cache_dir, id, &snapshot_id)
+}
+
+// The cache only saves fetching things again, so not being able to write it isn't worth an error
+fn save_summaries(store: &Store, config: &Config) {
+    cache::save_summaries(&config.library_cache_dir, &store.playlist_summaries()).ok();
+}
+
+fn save_playlist(store: &Store, config: &Config, id: &str) {
+    if let Some(p) = store.playlists().get(id) {
+        cache::save_playlist(&config.library_cache_dir, p).ok();
+    }
+}
@@ -0,0 +1,276 @@
+// Sits between Store::send_request and the backend. Only a few requests go out at once,
+// nothing goes out while Spotify has asked to slow down, reads that fail for reasons that might
+// pass are tried again, and asking for something that's already being fetched doesn't fetch it
+// twice
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use rand::Rng;
+use tokio::sync::Semaphore;
+use tokio::time;
+
+use crate::api::TransientError;
+use crate::app::NetworkRequest;
+
+const MAX_IN_FLIGHT: usize = 4;
+// Including the first try
+const MAX_ATTEMPTS: u32 = 4;
+// Doubled after each failed try
+const BASE_DELAY: Duration = Duration::from_millis(500);
+// For when Spotify says to slow down without saying for how long
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+pub struct Scheduler {
+    slots: Semaphore,
+    // Reads that are going right now, and whether the same one was asked for again since
+    in_flight: Mutex<HashMap<NetworkRequest, bool>>,
+    // Nothing new starts until then, after Spotify said to slow down
+    paused_until: Mutex<Option<Instant>>,
+    base_delay: Duration,
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler::with_base_delay(BASE_DELAY)
+    }
+
+    pub(crate) fn with_base_delay(base_delay: Duration) -> Scheduler {
+        Scheduler {
+            slots: Semaphore::new(MAX_IN_FLIGHT),
+            in_flight: Mutex::new(HashMap::new()),
+            paused_until: Mutex::new(None),
+            base_delay,
+        }
+    }
+
+    // Carries out `r` with `handle`. If the same read is already going this returns right
+    // away, and for reads of what's current, like the playback status, the one going runs
+    // once more afterwards so nothing that changed in between is missed
+    pub async fn run<F, Fut>(&self, r: NetworkRequest, handle: F) -> Result<()>
+    where
+        F: Fn(NetworkRequest) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        if !is_read(&r) {
+            return self.attempt(false, || handle(r.clone())).await;
+        }
+
+        {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            if let Some(again) = in_flight.get_mut(&r) {
+                *again = *again || is_current_state(&r);
+                return Ok(());
+            }
+            in_flight.insert(r.clone(), false);
+        }
+
+        loop {
+            let result = self.attempt(true, || handle(r.clone())).await;
+
+            let mut in_flight = self.in_flight.lock().unwrap();
+            if result.is_ok() && in_flight.get(&r) == Some(&true) {
+                in_flight.insert(r.clone(), false);
+            } else {
+                in_flight.remove(&r);
+                return result;
+            }
+        }
+    }
+
+    // A read whose answer is needed right where it's asked for, rather than put in the store.
+    // It's tried again like any other read, but isn't checked against the ones already going
+    pub async fn read<T, F, Fut>(&self, fetch: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.attempt(true, fetch).await
+    }
+
+    async fn attempt<T, F, Fut>(&self, read: bool, handle: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempts = 0;
+        loop {
+            self.wait_if_paused().await;
+            let result = {
+                let _slot = self.slots.acquire().await;
+                handle().await
+            };
+            let e = match result {
+                Ok(t) => return Ok(t),
+                Err(e) => e,
+            };
+
+            attempts += 1;
+            if attempts >= MAX_ATTEMPTS {
+                return Err(e);
+            }
+            match e.downcast_ref::<TransientError>() {
+                // Spotify turned it away without doing anything, so even a write can go again
+                Some(TransientError::RateLimited(after)) => {
+                    self.pause_for(after.unwrap_or(DEFAULT_RETRY_AFTER));
+                }
+                // Otherwise only reads, since a write might have happened before it failed
+                Some(_) if read => time::delay_for(self.backoff(attempts)).await,
+                _ => return Err(e),
+            }
+        }
+    }
+
+    async fn wait_if_paused(&self) {
+        let until = *self.paused_until.lock().unwrap();
+        if let Some(until) = until {
+            let now = Instant::now();
+            if until > now {
+                time::delay_for(until - now).await;
+            }
+        }
+    }
+
+    fn pause_for(&self, duration: Duration) {
+        let until = Instant::now() + duration;
+        let mut paused_until = self.paused_until.lock().unwrap();
+        if paused_until.map(|p| p < until).unwrap_or(true) {
+            *paused_until = Some(until);
+        }
+    }
+
+    // Somewhere between half and all of the doubled delay, so retries from
+    // requests that failed together don't all go out together again
+    fn backoff(&self, attempts: u32) -> Duration {
+        let max = self.base_delay.as_millis() as u64 * 2u64.pow(attempts - 1);
+        Duration::from_millis(rand::thread_rng().gen_range(max / 2, max + 1))
+    }
+}
+
+// Requests that only fetch things, so sending them twice does no harm
+fn is_read(r: &NetworkRequest) -> bool {
+    matches!(
+        r,
+        NetworkRequest::LoadNextPage(_)
+            | NetworkRequest::LoadPlaylist(_)
+            | NetworkRequest::GetDevices
+            | NetworkRequest::GetPlaybackStatus
+            | NetworkRequest::Search(_)
+    )
+}
+
+// Reads whose answer can change from one moment to the next. The rest, like loading the next
+// page, would fetch something else if they ran again
+fn is_current_state(r: &NetworkRequest) -> bool {
+    matches!(r, NetworkRequest::GetDevices | NetworkRequest::GetPlaybackStatus)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::api::PageId;
+
+    // Stands in for the backend. Gives whatever else is waiting a chance to run while the request
+    // is "on the network", then answers with whatever `answer` says for that call, counting from 0
+    async fn respond(calls: &AtomicUsize, answer: fn(usize) -> Result<()>) -> Result<()> {
+        let call = calls.fetch_add(1, Ordering::SeqCst);
+        for _ in 0..5 {
+            let _ = tokio::task::yield_now().await;
+        }
+        answer(call)
+    }
+
+    #[tokio::test]
+    async fn duplicate_page_loads_are_dropped() {
+        let scheduler = Scheduler::new();
+        let calls = &AtomicUsize::new(0);
+        let handle = move |_: NetworkRequest| respond(calls, |_| Ok(()));
+
+        let r = NetworkRequest::LoadNextPage(PageId::Playlists);
+        let (first, second) = tokio::join!(
+            scheduler.run(r.clone(), &handle),
+            scheduler.run(r.clone(), &handle),
+        );
+        first.unwrap();
+        second.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Once it's done, the next one goes through
+        scheduler.run(r, &handle).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn duplicate_status_checks_run_once_more() {
+        let scheduler = Scheduler::new();
+        let calls = &AtomicUsize::new(0);
+        let handle = move |_: NetworkRequest| respond(calls, |_| Ok(()));
+
+        let r = NetworkRequest::GetPlaybackStatus;
+        let (a, b, c) = tokio::join!(
+            scheduler.run(r.clone(), &handle),
+            scheduler.run(r.clone(), &handle),
+            scheduler.run(r.clone(), &handle),
+        );
+        a.and(b).and(c).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn reads_are_retried() {
+        let scheduler = Scheduler::with_base_delay(Duration::from_millis(1));
+        let calls = &AtomicUsize::new(0);
+        let handle = move |_: NetworkRequest| respond(calls, |call| match call {
+            0 => Err(TransientError::Server(503).into()),
+            1 => Err(TransientError::Network("timed out".to_owned()).into()),
+            _ => Ok(()),
+        });
+
+        scheduler.run(NetworkRequest::GetDevices, &handle).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn reads_give_up_eventually() {
+        let scheduler = Scheduler::with_base_delay(Duration::from_millis(1));
+        let calls = &AtomicUsize::new(0);
+        let handle = move |_: NetworkRequest| respond(calls, |_| Err(TransientError::Server(500).into()));
+
+        assert!(scheduler.run(NetworkRequest::GetDevices, &handle).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), MAX_ATTEMPTS as usize);
+    }
+
+    #[tokio::test]
+    async fn writes_are_only_retried_when_rate_limited() {
+        let scheduler = Scheduler::with_base_delay(Duration::from_millis(1));
+        let calls = &AtomicUsize::new(0);
+        let handle = move |_: NetworkRequest| respond(calls, |_| Err(TransientError::Server(502).into()));
+        assert!(scheduler.run(NetworkRequest::Pause, &handle).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let calls = &AtomicUsize::new(0);
+        let handle = move |_: NetworkRequest| respond(calls, |call| match call {
+            0 => Err(TransientError::RateLimited(Some(Duration::from_millis(20))).into()),
+            _ => Ok(()),
+        });
+        let start = Instant::now();
+        scheduler.run(NetworkRequest::Pause, &handle).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[tokio::test]
+    async fn other_errors_are_not_retried() {
+        let scheduler = Scheduler::with_base_delay(Duration::from_millis(1));
+        let calls = &AtomicUsize::new(0);
+        let handle = move |_: NetworkRequest| respond(calls, |_| Err(anyhow!("No active device")));
+
+        assert!(scheduler.run(NetworkRequest::GetPlaybackStatus, &handle).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,204 @@
+// Everything spotivi knows about the library and playback, and the way to ask for more.
+// main makes one and hands it to whatever needs it. Changes go through the methods here,
+// which tell every subscriber what changed
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::api::{
+    Device,
+    Paged,
+    PageId,
+    PlaybackState,
+    Playlist,
+    PlaylistSummary,
+    PlaylistTrack,
+    SearchPage,
+    SearchResults,
+    PAGE_SIZE,
+};
+use crate::app::NetworkRequest;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StoreEvent {
+    // A whole playlist, newly loaded or loaded again
+    PlaylistAdded(String),
+    // More of a list, or all of it again, like after the playlists were checked against Spotify
+    PageLoaded(PageId),
+    PlaybackChanged,
+}
+
+// What send_request sends, for network::handle_requests to carry out
+pub type Requests = UnboundedReceiver<NetworkRequest>;
+pub type Events = UnboundedReceiver<StoreEvent>;
+
+pub struct Store {
+    playlist_summaries: Mutex<Paged<PlaylistSummary>>,
+    playlists: Mutex<HashMap<String, Playlist>>,
+    devices: Mutex<Paged<Device>>,
+    search_results: Mutex<SearchResults>,
+    playback: Mutex<Option<PlaybackState>>,
+    requests: UnboundedSender<NetworkRequest>,
+    subscribers: Mutex<Vec<UnboundedSender<StoreEvent>>>,
+}
+
+impl Store {
+    pub fn new() -> (Store, Requests) {
+        let (requests, rx) = mpsc::unbounded_channel();
+        let store = Store {
+            playlist_summaries: Mutex::new(Paged::new(PageId::Playlists)),
+            playlists: Mutex::new(HashMap::new()),
+            devices: Mutex::new(Paged::new(PageId::Devices)),
+            search_results: Mutex::new(SearchResults::new(String::new())),
+            playback: Mutex::new(None),
+            requests,
+            subscribers: Mutex::new(Vec::new()),
+        };
+        (store, rx)
+    }
+
+    pub fn send_request(&self, r: NetworkRequest) {
+        // Only fails once nothing is handling requests, when spotivi is quitting anyway
+        self.requests.send(r).ok();
+    }
+
+    // Every change from now on
+    pub fn subscribe(&self) -> Events {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    fn publish(&self, event: StoreEvent) {
+        // Subscribers that went away are dropped
+        self.subscribers.lock().unwrap().retain(|s| s.send(event.clone()).is_ok());
+    }
+
+    // These are for reading. Changing what they hold wouldn't tell the subscribers,
+    // so that goes through the methods below
+
+    pub fn playlist_summaries(&self) -> MutexGuard<'_, Paged<PlaylistSummary>> {
+        self.playlist_summaries.lock().unwrap()
+    }
+
+    pub fn playlists(&self) -> MutexGuard<'_, HashMap<String, Playlist>> {
+        self.playlists.lock().unwrap()
+    }
+
+    pub fn devices(&self) -> MutexGuard<'_, Paged<Device>> {
+        self.devices.lock().unwrap()
+    }
+
+    pub fn search_results(&self) -> MutexGuard<'_, SearchResults> {
+        self.search_results.lock().unwrap()
+    }
+
+    pub fn playback(&self) -> MutexGuard<'_, Option<PlaybackState>> {
+        self.playback.lock().unwrap()
+    }
+
+    pub fn add_playlist(&self, p: Playlist) {
+        let id = p.id().to_owned();
+        self.playlists.lock().unwrap().insert(id.clone(), p);
+        self.publish(StoreEvent::PlaylistAdded(id));
+    }
+
+    pub fn add_playlist_tracks(&self, id: &str, offset: usize, tracks: Paged<PlaylistTrack>) {
+        if let Some(p) = self.playlists.lock().unwrap().get_mut(id) {
+            p.add_tracks(offset, tracks);
+        }
+        self.publish(StoreEvent::PageLoaded(PageId::Playlist(id.to_owned())));
+    }
+
+    // `page` is the page number, so a page that was requested twice only gets added once
+    pub fn add_playlist_summaries(&self, page: u32, p: Paged<PlaylistSummary>) {
+        {
+            let mut playlists = self.playlist_summaries.lock().unwrap();
+            if playlists.len() == (page * PAGE_SIZE) as usize {
+                playlists.add_page(p);
+            }
+        }
+        self.publish(StoreEvent::PageLoaded(PageId::Playlists));
+    }
+
+    pub fn set_playlist_summaries(&self, p: Paged<PlaylistSummary>) {
+        *self.playlist_summaries.lock().unwrap() = p;
+        self.publish(StoreEvent::PageLoaded(PageId::Playlists));
+    }
+
+    pub fn set_devices(&self, devices: Vec<Device>) {
+        *self.devices.lock().unwrap() = Paged::from_items(devices, PageId::Devices);
+        self.publish(StoreEvent::PageLoaded(PageId::Devices));
+    }
+
+    // Whoever starts a search is already showing it, so there's nothing to tell
+    pub fn new_search(&self, query: String) {
+        *self.search_results.lock().unwrap() = SearchResults::new(query);
+    }
+
+    pub fn add_search_page(&self, query: &str, offset: usize, page: SearchPage) {
+        let kind = page.kind();
+        self.search_results.lock().unwrap().add_page(query, offset, page);
+        self.publish(StoreEvent::PageLoaded(PageId::Search { query: query.to_owned(), kind }));
+    }
+
+    pub fn set_playback(&self, p: Option<PlaybackState>) {
+        *self.playback.lock().unwrap() = p;
+        self.publish(StoreEvent::PlaybackChanged);
+    }
+
+    // Changes what's playing in place, like after seeking. Nothing happens if nothing is playing
+    pub fn update_playback(&self, f: impl FnOnce(&mut PlaybackState)) {
+        let changed = match *self.playback.lock().unwrap() {
+            Some(ref mut p) => {
+                f(p);
+                true
+            }
+            None => false,
+        };
+        if changed {
+            self.publish(StoreEvent::PlaybackChanged);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_subscriber_hears_about_changes() {
+        let (store, _requests) = Store::new();
+        let mut first = store.subscribe();
+        let mut second = store.subscribe();
+
+        store.set_devices(Vec::new());
+        store.set_playback(None);
+        for events in [&mut first, &mut second].iter_mut() {
+            assert_eq!(events.try_recv().ok(), Some(StoreEvent::PageLoaded(PageId::Devices)));
+            assert_eq!(events.try_recv().ok(), Some(StoreEvent::PlaybackChanged));
+            assert!(events.try_recv().is_err());
+        }
+
+        // Nothing is playing, so there's nothing to change
+        store.update_playback(|p| p.set_shuffle(true));
+        assert!(first.try_recv().is_err());
+    }
+
+    #[test]
+    fn stores_are_separate() {
+        let (a, mut a_requests) = Store::new();
+        let (b, mut b_requests) = Store::new();
+        let mut b_events = b.subscribe();
+
+        a.send_request(NetworkRequest::GetDevices);
+        a.new_search("drivers".to_owned());
+        a.set_playback(None);
+        assert_eq!(a_requests.try_recv().ok(), Some(NetworkRequest::GetDevices));
+        assert!(b_requests.try_recv().is_err());
+        assert_eq!(b.search_results().query(), "");
+        assert!(b_events.try_recv().is_err());
+    }
+}
@@ -0,0 +1,307 @@
+use std::fs;
+
+use crate::api::FakeBackend;
+use crate::app::Action;
+use crate::keybindings::parse_keys;
+use crate::tests::{library, library_with_focus, temp_dir, Harness};
+use crate::views::ScreenId;
+
+fn playing_title(h: &Harness) -> Option<String> {
+    h.store.playback()
+        .as_ref()
+        .and_then(|p| p.title().map(|t| t.to_owned()))
+}
+
+#[tokio::test]
+async fn opens_the_selected_playlist() {
+    let h = Harness::start(library(&[])).await;
+    assert_eq!(h.app.lock().unwrap().screen_id(), ScreenId::Playlists);
+
+    h.keys("j<CR>").await;
+    assert_eq!(h.app.lock().unwrap().screen_id(), ScreenId::Playlist("focus".to_owned()));
+
+    h.keys("<C-o>").await;
+    assert_eq!(h.app.lock().unwrap().screen_id(), ScreenId::Playlists);
+    assert_eq!(h.selected_index(), Some(1));
+}
+
+#[tokio::test]
+async fn plays_a_track_in_its_playlist() {
+    let h = Harness::start(library(&[])).await;
+
+    h.keys("<CR>jj<CR>").await;
+    assert_eq!(h.calls(), vec!["play spotify:playlist:road at 2"]);
+    assert_eq!(playing_title(&h).as_deref(), Some("Last Exit"));
+
+    h.keys("<<").await;
+    assert_eq!(playing_title(&h).as_deref(), Some("Highway Song"));
+}
+
+#[tokio::test]
+async fn space_toggles_playback() {
+    let h = Harness::start(library(&[])).await;
+    h.keys("<CR><CR>").await;
+    assert!(h.store.playback().as_ref().unwrap().is_playing());
+
+    h.keys(" ").await;
+    assert!(!h.store.playback().as_ref().unwrap().is_playing());
+    h.keys(" ").await;
+    assert!(h.store.playback().as_ref().unwrap().is_playing());
+    assert_eq!(h.calls()[1..], ["pause", "resume"]);
+}
+
+#[tokio::test]
+async fn r_restarts_the_track() {
+    let h = Harness::start(library(&[])).await;
+    h.keys("<CR><CR><A-5>").await;
+    assert_eq!(h.calls()[1..], ["seek 90000"]);
+
+    h.keys("r").await;
+    assert_eq!(h.calls()[2..], ["seek 0"]);
+    assert_eq!(h.store.playback().as_ref().unwrap().progress_ms(), 0);
+}
+
+#[tokio::test]
+async fn counts_repeat_motions() {
+    let backend = FakeBackend::new().numbered_playlist("long", "Long", 20);
+    let h = Harness::start(backend).await;
+
+    h.keys("<CR>3j").await;
+    assert_eq!(h.selected_index(), Some(3));
+    h.keys("2k").await;
+    assert_eq!(h.selected_index(), Some(1));
+}
+
+#[tokio::test]
+async fn bottom_loads_every_page_of_a_playlist() {
+    let backend = FakeBackend::new().numbered_playlist("long", "Long", 120);
+    let h = Harness::start(backend).await;
+
+    h.keys("<CR>G").await;
+    assert_eq!(h.selected_index(), Some(119));
+
+    h.keys("gg").await;
+    assert_eq!(h.selected_index(), Some(0));
+}
+
+#[tokio::test]
+async fn bottom_keeps_loading_after_going_back() {
+    let backend = FakeBackend::new().numbered_playlist("long", "Long", 120);
+    let h = Harness::start(backend).await;
+
+    // The pages come in while the playlists are showing
+    h.keys("<CR>").await;
+    h.keys_at_once("G<C-o>").await;
+    assert_eq!(h.app.lock().unwrap().screen_id(), ScreenId::Playlists);
+    assert_eq!(h.store.playlists()["long"].items().len(), 120);
+
+    h.keys("<Tab>").await;
+    assert_eq!(h.selected_index(), Some(119));
+}
+
+#[tokio::test]
+async fn bottom_loads_every_page_of_playlists() {
+    let backend = (0..60).fold(FakeBackend::new(), |b, i| {
+        b.playlist(&format!("p{}", i), &format!("Playlist {}", i), &[])
+    });
+    let h = Harness::start(backend).await;
+    assert_eq!(h.store.playlist_summaries().len(), 50);
+
+    h.keys("G").await;
+    assert_eq!(h.store.playlist_summaries().len(), 60);
+    assert_eq!(h.selected_index(), Some(59));
+}
+
+#[tokio::test]
+async fn finds_within_the_list() {
+    let h = Harness::start(library(&[])).await;
+
+    h.keys("<CR>/drivers<CR>").await;
+    assert_eq!(h.selected_index(), Some(2));
+    // Wraps around to the top
+    h.keys("n").await;
+    assert_eq!(h.selected_index(), Some(0));
+    h.keys("N").await;
+    assert_eq!(h.selected_index(), Some(2));
+}
+
+#[tokio::test]
+async fn a_playlist_that_fails_to_load_isnt_opened_later() {
+    let h = Harness::start(library(&[])).await;
+
+    h.app.lock().unwrap().handle_action(Action::OpenPlaylist("missing".to_owned())).unwrap();
+    h.settle().await;
+    assert!(h.app.lock().unwrap().message().unwrap().starts_with("Error"));
+    assert_eq!(h.app.lock().unwrap().opening(), None);
+    assert_eq!(h.app.lock().unwrap().screen_id(), ScreenId::Playlists);
+}
+
+#[tokio::test]
+async fn going_elsewhere_while_a_playlist_loads_stays_there() {
+    let h = Harness::start(library(&[])).await;
+    h.keys("j<CR><C-o>").await;
+
+    // Loaded only after tab has already gone forward again
+    h.app.lock().unwrap().handle_action(Action::OpenPlaylist("road".to_owned())).unwrap();
+    h.app.lock().unwrap().handle_event(parse_keys("<Tab>").unwrap().remove(0)).unwrap();
+    h.settle().await;
+    assert_eq!(h.app.lock().unwrap().screen_id(), ScreenId::Playlist("focus".to_owned()));
+}
+
+#[tokio::test]
+async fn device_command_transfers_playback() {
+    let h = Harness::start(library(&[])).await;
+
+    h.keys(":device<Space>kitchen<CR>").await;
+    assert_eq!(h.calls(), vec!["transfer kitchen"]);
+}
+
+#[tokio::test]
+async fn open_completes_playlists_by_name() {
+    let h = Harness::start(library(&[])).await;
+
+    h.keys(":open<Space>fo<Tab><CR>").await;
+    assert_eq!(h.app.lock().unwrap().screen_id(), ScreenId::Playlist("focus".to_owned()));
+}
+
+#[tokio::test]
+async fn commands_run_even_if_the_history_cant_be_saved() {
+    let h = Harness::start(library(&[])).await;
+    // Writing the history fails with a directory in the way
+    std::fs::create_dir_all(&h.config().command_history_path).unwrap();
+
+    h.keys(":device<Space>kitchen<CR>").await;
+    assert_eq!(h.calls(), vec!["transfer kitchen"]);
+    assert!(h.app.lock().unwrap().message().unwrap().starts_with("Couldn't save history"));
+}
+
+#[tokio::test]
+async fn info_popup_shows_the_track_and_any_key_closes_it() {
+    let h = Harness::start(library(&[])).await;
+
+    h.keys("<CR>jK").await;
+    let lines = h.app.lock().unwrap().popup().map(|p| p.lines.clone());
+    assert_eq!(lines, Some(vec!["Title: Blue Sky".to_owned(), "Artist: Clouds".to_owned()]));
+
+    h.keys("j").await;
+    assert!(h.app.lock().unwrap().popup().is_none());
+    // The key only closed the popup
+    assert_eq!(h.selected_index(), Some(1));
+}
+
+#[tokio::test]
+async fn errors_are_shown_and_kept_for_messages() {
+    // Without an active device there's nowhere to play
+    let backend = FakeBackend::new().playlist("road", "Road Trip", &[("Highway Song", "The Drivers")]);
+    let h = Harness::start(backend).await;
+
+    h.keys("<CR><CR>").await;
+    let error = "Error starting playback: No active device";
+    assert_eq!(h.app.lock().unwrap().message(), Some(error));
+
+    h.keys("k").await;
+    assert_eq!(h.app.lock().unwrap().message(), None);
+
+    h.keys(":messages<CR>").await;
+    let lines = h.app.lock().unwrap().popup().map(|p| p.lines.clone());
+    assert_eq!(lines, Some(vec![error.to_owned()]));
+}
+
+#[tokio::test]
+async fn only_changed_playlists_are_fetched_after_a_restart() {
+    let h = Harness::start(library(&[])).await;
+    h.keys("<CR><C-o>j<CR>").await;
+    assert_eq!(h.fetches(), vec!["playlists 0", "playlist road", "playlist focus"]);
+
+    let h = h.restart(library_with_focus(&[("Quiet", "Nobody"), ("Still", "Nobody")], &[])).await;
+    assert_eq!(h.fetches(), vec!["playlists 0"]);
+
+    h.keys("<CR>").await;
+    assert_eq!(h.app.lock().unwrap().screen_id(), ScreenId::Playlist("road".to_owned()));
+    assert_eq!(h.fetches(), vec!["playlists 0"]);
+
+    h.keys("<C-o>j<CR>").await;
+    assert_eq!(h.fetches(), vec!["playlists 0", "playlist focus"]);
+    assert_eq!(h.store.playlists()["focus"].items().len(), 2);
+}
+
+#[tokio::test]
+async fn browses_saved_playlists_when_spotify_cant_be_reached() {
+    let h = Harness::start(library(&[])).await;
+    h.keys("<CR>").await;
+
+    let h = h.restart(library(&[]).unreachable()).await;
+    assert!(h.app.lock().unwrap().message().unwrap().starts_with("Offline"));
+    assert_eq!(h.store.playlist_summaries().len(), 2);
+
+    h.keys("<CR>").await;
+    assert_eq!(h.app.lock().unwrap().screen_id(), ScreenId::Playlist("road".to_owned()));
+    h.keys("<CR>").await;
+    assert_eq!(
+        h.app.lock().unwrap().message(),
+        Some("Error starting playback: Spotify can't be reached, so nothing can be played while offline"),
+    );
+    assert!(h.calls().is_empty());
+
+    h.keys(":search<Space>drivers<CR>").await;
+    assert_eq!(h.store.search_results().tracks.len(), 2);
+}
+
+#[tokio::test]
+async fn exports_saved_playlists_while_offline() {
+    let h = Harness::start(library(&[])).await;
+    h.keys("<CR>").await;
+
+    let h = h.restart(library(&[]).unreachable()).await;
+    let dir = temp_dir();
+    let path = dir.join("playlists.txt");
+    h.keys(&format!(":export<Space>{}<CR>", path.display())).await;
+    assert_eq!(
+        h.app.lock().unwrap().message(),
+        Some(&format!("Exported 1 playlist to {}", path.display())[..]),
+    );
+
+    // Only the road trip was opened, so it's the only one with its tracks saved
+    let text = fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "Road Trip\tspotify:playlist:road");
+    assert!(lines[1].starts_with("\tHighway Song\tThe Drivers\tspotify:track:"));
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[tokio::test]
+async fn stays_online_when_spotify_is_only_busy() {
+    let h = Harness::start(library(&[])).await;
+    h.keys("<CR>").await;
+
+    // Tried again rather than taken to mean Spotify can't be reached
+    let h = h.restart(library(&[]).busy(2)).await;
+    assert_eq!(h.app.lock().unwrap().message(), None);
+    assert_eq!(h.fetches(), vec!["playlists 0"]);
+
+    h.keys("<CR><CR>").await;
+    assert_eq!(h.calls(), vec!["play spotify:playlist:road at 0"]);
+}
+
+#[tokio::test]
+async fn screens_with_no_room_left_still_take_keys() {
+    // The status bar takes up the whole terminal
+    let h = Harness::start_with_size(library(&[]), 20, 2).await;
+
+    h.keys("j<CR>G").await;
+    assert_eq!(h.app.lock().unwrap().screen_id(), ScreenId::Playlist("focus".to_owned()));
+    h.keys(":search<Space>drivers<CR>j").await;
+    assert_eq!(h.store.search_results().tracks.len(), 2);
+}
+
+#[tokio::test]
+async fn a_one_line_terminal_only_shows_whats_playing() {
+    let h = Harness::start_with_size(library(&[]), 40, 1).await;
+
+    h.keys("<CR>jj<CR>").await;
+    let screen = h.screen();
+    // The line after the size and the header, which the progress bar used to overwrite
+    let line = screen.lines().nth(2).unwrap();
+    assert!(line.starts_with("> Last Exit"), "{}", line);
+}
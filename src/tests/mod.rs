@@ -0,0 +1,238 @@
+// Drives App with keys against FakeBackend, the way main does with a terminal and Spotify.
+// What gets drawn goes to a CellGrid, and can be compared with the snapshots in snapshots/
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::RwLock;
+
+use crate::api::{FakeBackend, MusicBackend, PlaybackState};
+use crate::app::{App, NetworkRequest};
+use crate::config::Config;
+use crate::keybindings::parse_keys;
+use crate::network::{self, handle_request, Backend};
+use crate::scheduler::Scheduler;
+use crate::store::{Events, Requests, Store};
+use crate::views::{CellGrid, Output};
+
+mod app;
+mod snapshots;
+
+static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+// What most tests start with: a road trip, a focus playlist, and then the `extra` playlists,
+// given as (id, name), with no tracks. The laptop is what's playing
+pub fn library(extra: &[(&str, &str)]) -> FakeBackend {
+    library_with_focus(&[("Quiet", "Nobody")], extra)
+}
+
+// The same, with different tracks in the focus playlist, like after it was changed
+pub fn library_with_focus(focus: &[(&str, &str)], extra: &[(&str, &str)]) -> FakeBackend {
+    let mut backend = FakeBackend::new()
+        .playlist("road", "Road Trip", &[
+            ("Highway Song", "The Drivers"),
+            ("Blue Sky", "Clouds"),
+            ("Last Exit", "The Drivers"),
+        ])
+        .playlist("focus", "Focus", focus);
+    for &(id, name) in extra {
+        backend = backend.playlist(id, name, &[]);
+    }
+    backend
+        .device("laptop", "Laptop", true)
+        .device("kitchen", "Kitchen Speaker", false)
+}
+
+// An empty directory of its own for each test's config and cache. Left for the caller to remove
+pub fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "spotivi-test-{}-{}",
+        std::process::id(),
+        NEXT_DIR.fetch_add(1, Ordering::SeqCst),
+    ));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// What the playback state is once the road trip from library() starts playing on the laptop
+pub async fn road_trip_playing() -> PlaybackState {
+    let mut backend = library(&[]);
+    backend.play_context("spotify:playlist:road".to_owned(), None).await.unwrap();
+    backend.playback_status().await.unwrap().unwrap()
+}
+
+pub struct Harness {
+    pub app: Arc<Mutex<App>>,
+    pub store: Arc<Store>,
+    // What the backend was asked to do, see FakeBackend::calls
+    pub calls: Arc<Mutex<Vec<String>>>,
+    fetches: Arc<Mutex<Vec<String>>>,
+    grid: Arc<Mutex<CellGrid>>,
+    // The ones main would hand to network::handle_requests and app::watch_store
+    requests: Mutex<Requests>,
+    events: Mutex<Events>,
+    scheduler: Arc<Scheduler>,
+    api: Backend,
+    config: Arc<Config>,
+    dir: PathBuf,
+}
+
+impl Harness {
+    pub async fn start(backend: FakeBackend) -> Harness {
+        Harness::start_with_size(backend, 80, 24).await
+    }
+
+    pub async fn start_with_size(backend: FakeBackend, width: u16, height: u16) -> Harness {
+        Harness::start_in(backend, temp_dir(), width, height).await
+    }
+
+    // Quits and starts again with the same config dir, and so the same cache
+    pub async fn restart(mut self, backend: FakeBackend) -> Harness {
+        let dir = std::mem::take(&mut self.dir);
+        drop(self);
+        Harness::start_in(backend, dir, 80, 24).await
+    }
+
+    // Loads the first page of playlists and the devices, like starting spotivi does
+    async fn start_in(backend: FakeBackend, dir: PathBuf, width: u16, height: u16) -> Harness {
+        let grid = Arc::new(Mutex::new(CellGrid::new(width, height)));
+        let config = Arc::new(Config::for_tests(&dir).unwrap());
+
+        let calls = backend.calls();
+        let fetches = backend.fetches();
+        let api = Arc::new(RwLock::new(Box::new(backend) as Box<dyn MusicBackend>));
+        let (store, requests) = Store::new();
+        let store = Arc::new(store);
+        let events = store.subscribe();
+        let app = Arc::new(Mutex::new(App::new(Arc::clone(&config), Arc::clone(&store), Output::Grid(Arc::clone(&grid)))));
+        let harness = Harness {
+            app,
+            store,
+            calls,
+            fetches,
+            grid,
+            requests: Mutex::new(requests),
+            events: Mutex::new(events),
+            // Retries that don't slow the tests down
+            scheduler: Arc::new(Scheduler::with_base_delay(Duration::from_millis(1))),
+            api,
+            config,
+            dir,
+        };
+
+        network::init(
+            Arc::clone(&harness.scheduler),
+            Arc::clone(&harness.app),
+            Arc::clone(&harness.api),
+            Arc::clone(&harness.store),
+            Arc::clone(&harness.config),
+        ).await.unwrap();
+        harness.request(NetworkRequest::GetDevices).await;
+        harness
+    }
+
+    // Keys in vim's notation, like "3j<CR>". Whatever each key sends is handled before the next one
+    pub async fn keys(&self, keys: &str) {
+        for key in parse_keys(keys).unwrap() {
+            self.app.lock().unwrap().handle_event(key).unwrap();
+            self.settle().await;
+        }
+    }
+
+    // The same, but all the keys are handled before anything they sent, like typing faster than
+    // Spotify answers
+    pub async fn keys_at_once(&self, keys: &str) {
+        for key in parse_keys(keys).unwrap() {
+            self.app.lock().unwrap().handle_event(key).unwrap();
+        }
+        self.settle().await;
+    }
+
+    pub async fn request(&self, r: NetworkRequest) {
+        self.store.send_request(r);
+        self.settle().await;
+    }
+
+    // Handles requests one at a time until none are left, so tests always see the same order.
+    // The app hears about each change to the store before the next request goes out
+    pub async fn settle(&self) {
+        loop {
+            // Tasks the app started, like the one that sends the volume, need a chance to run
+            for _ in 0..10 {
+                let _ = tokio::task::yield_now().await;
+            }
+            loop {
+                let event = self.events.lock().unwrap().try_recv();
+                match event {
+                    Ok(event) => self.app.lock().unwrap().handle_store_event(event).unwrap(),
+                    Err(_) => break,
+                }
+            }
+            let r = self.requests.lock().unwrap().try_recv();
+            let r = match r {
+                Ok(r) => r,
+                Err(_) => return,
+            };
+            handle_request(
+                r,
+                Arc::clone(&self.scheduler),
+                Arc::clone(&self.api),
+                Arc::clone(&self.app),
+                Arc::clone(&self.store),
+                Arc::clone(&self.config),
+            ).await;
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    // See FakeBackend::fetches
+    pub fn fetches(&self) -> Vec<String> {
+        self.fetches.lock().unwrap().clone()
+    }
+
+    pub fn selected_index(&self) -> Option<usize> {
+        self.app.lock().unwrap().selected_index()
+    }
+
+    // Everything on screen, see CellGrid::to_text
+    pub fn screen(&self) -> String {
+        self.grid.lock().unwrap().to_text()
+    }
+
+    // Compares the screen with snapshots/<name>.txt. With UPDATE_SNAPSHOTS set, the file is
+    // written instead, to be looked over and checked in
+    pub fn assert_snapshot(&self, name: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/tests/snapshots")
+            .join(format!("{}.txt", name));
+        let screen = self.screen();
+
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::write(&path, screen).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+            panic!("{} doesn't exist, run the tests with UPDATE_SNAPSHOTS=1 to make it", path.display())
+        });
+        assert!(expected == screen, "{} doesn't match the screen:\n{}", path.display(), screen);
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        // Empty if the dir was handed on by restart
+        if !self.dir.as_os_str().is_empty() {
+            fs::remove_dir_all(&self.dir).ok();
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use crate::tests::{library, Harness};
+
+// Enough playlists to scroll
+const MORE_PLAYLISTS: &[(&str, &str)] = &[
+    ("night", "Late Night"),
+    ("gym", "Gym"),
+    ("sleep", "Sleep"),
+    ("rain", "Rainy Day"),
+];
+
+#[tokio::test]
+async fn playlists() {
+    let h = Harness::start_with_size(library(MORE_PLAYLISTS), 40, 10).await;
+    h.keys("j").await;
+    h.assert_snapshot("playlists_40x10");
+}
+
+#[tokio::test]
+async fn playlists_scrolled() {
+    let h = Harness::start_with_size(library(MORE_PLAYLISTS), 20, 6).await;
+    h.keys("4j").await;
+    h.assert_snapshot("playlists_scrolled_20x6");
+}
+
+#[tokio::test]
+async fn playlist_with_find_matches() {
+    let h = Harness::start_with_size(library(MORE_PLAYLISTS), 40, 10).await;
+    h.keys("<CR>/drivers<CR>").await;
+    h.assert_snapshot("playlist_find_40x10");
+}
+
+#[tokio::test]
+async fn playlist_scrolled() {
+    let h = Harness::start_with_size(library(MORE_PLAYLISTS), 30, 5).await;
+    h.keys("<CR>G").await;
+    h.assert_snapshot("playlist_scrolled_30x5");
+}
+
+#[tokio::test]
+async fn playlist_popup() {
+    let h = Harness::start_with_size(library(MORE_PLAYLISTS), 60, 16).await;
+    h.keys("K").await;
+    h.assert_snapshot("playlist_popup_60x16");
+}
+
+#[tokio::test]
+async fn track_popup() {
+    let h = Harness::start_with_size(library(MORE_PLAYLISTS), 40, 12).await;
+    h.keys("<CR>jK").await;
+    h.assert_snapshot("track_popup_40x12");
+}
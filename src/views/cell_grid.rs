@@ -0,0 +1,248 @@
+use std::io::{self, Write};
+
+// A pretend terminal. Whatever crossterm writes to it is interpreted like a terminal would,
+// so what ends up on screen can be checked without one.
+// Only the escape sequences spotivi uses are understood, anything else is ignored
+#[derive(Debug)]
+pub struct CellGrid {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+    x: usize,
+    y: usize,
+    style: Style,
+    // The start of an escape sequence or character that was split between writes
+    pending: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Style {
+    bold: bool,
+    underlined: bool,
+    reversed: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Cell {
+    c: char,
+    style: Style,
+}
+
+const BLANK: Cell = Cell {
+    c: ' ',
+    style: Style { bold: false, underlined: false, reversed: false },
+};
+
+impl CellGrid {
+    pub fn new(width: u16, height: u16) -> CellGrid {
+        let (width, height) = (width as usize, height as usize);
+        CellGrid {
+            width,
+            height,
+            cells: vec![BLANK; width * height],
+            x: 0,
+            y: 0,
+            style: Style::default(),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        (self.width as u16, self.height as u16)
+    }
+
+    // The text on screen, then the styles of each cell in the same layout:
+    // r for reversed, u for underlined, b for bold and * for more than one
+    pub fn to_text(&self) -> String {
+        let mut text = format!("size {}x{}\n-- text --\n", self.width, self.height);
+        for row in self.cells.chunks(self.width) {
+            let line: String = row.iter().map(|c| c.c).collect();
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+        text.push_str("-- styles --\n");
+        for row in self.cells.chunks(self.width) {
+            let line: String = row.iter().map(|c| style_char(c.style)).collect();
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+        text
+    }
+
+    fn interpret(&mut self) {
+        let mut i = 0;
+        while i < self.pending.len() {
+            let used = if self.pending[i] == 0x1b {
+                self.escape_sequence(i)
+            } else {
+                self.character(i)
+            };
+            match used {
+                Some(n) => i += n,
+                // The rest hasn't been written yet
+                None => break,
+            }
+        }
+        self.pending.drain(..i);
+    }
+
+    // How many bytes the sequence at `start` took up
+    fn escape_sequence(&mut self, start: usize) -> Option<usize> {
+        let bytes = &self.pending[start..];
+        match bytes.get(1) {
+            Some(b'[') => {}
+            Some(_) => return Some(2),
+            None => return None,
+        }
+        let end = bytes.iter().skip(2).position(|b| (0x40..=0x7e).contains(b))? + 2;
+        let params = String::from_utf8_lossy(&bytes[2..end]).into_owned();
+        let command = bytes[end];
+        self.csi(&params, command);
+        Some(end + 1)
+    }
+
+    fn csi(&mut self, params: &str, command: u8) {
+        // Private sequences, like hiding the cursor, don't change what's on screen
+        if params.starts_with('?') {
+            return;
+        }
+        let numbers: Vec<usize> = params.split(';').map(|p| p.parse().unwrap_or(0)).collect();
+        let n = numbers[0];
+        let count = std::cmp::max(n, 1);
+
+        match command {
+            b'H' => {
+                let row = count;
+                let col = std::cmp::max(numbers.get(1).copied().unwrap_or(1), 1);
+                self.y = std::cmp::min(row - 1, self.height - 1);
+                self.x = std::cmp::min(col - 1, self.width - 1);
+            }
+            b'A' => self.y = self.y.saturating_sub(count),
+            b'B' => self.y = std::cmp::min(self.y + count, self.height - 1),
+            b'C' => self.x = std::cmp::min(self.x + count, self.width - 1),
+            b'D' => self.x = std::cmp::min(self.x, self.width - 1).saturating_sub(count),
+            b'E' => {
+                self.y = std::cmp::min(self.y + count, self.height - 1);
+                self.x = 0;
+            }
+            b'F' => {
+                self.y = self.y.saturating_sub(count);
+                self.x = 0;
+            }
+            b'G' => self.x = std::cmp::min(count - 1, self.width - 1),
+            b'J' => {
+                let here = self.y * self.width + std::cmp::min(self.x, self.width - 1);
+                match n {
+                    0 => self.clear(here, self.cells.len()),
+                    1 => self.clear(0, here + 1),
+                    _ => self.clear(0, self.cells.len()),
+                }
+            }
+            b'K' => {
+                let line = self.y * self.width;
+                let here = line + std::cmp::min(self.x, self.width - 1);
+                match n {
+                    0 => self.clear(here, line + self.width),
+                    1 => self.clear(line, here + 1),
+                    _ => self.clear(line, line + self.width),
+                }
+            }
+            b'm' => self.set_style(&numbers),
+            _ => {}
+        }
+    }
+
+    fn set_style(&mut self, codes: &[usize]) {
+        let mut codes = codes.iter().copied();
+        while let Some(code) = codes.next() {
+            match code {
+                0 => self.style = Style::default(),
+                1 => self.style.bold = true,
+                4 => self.style.underlined = true,
+                7 => self.style.reversed = true,
+                22 => self.style.bold = false,
+                24 => self.style.underlined = false,
+                27 => self.style.reversed = false,
+                // Colors, which take up more codes after them
+                38 | 48 | 58 => {
+                    let skip = match codes.next() {
+                        Some(5) => 1,
+                        Some(2) => 3,
+                        _ => 0,
+                    };
+                    codes.by_ref().take(skip).for_each(drop);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn clear(&mut self, from: usize, to: usize) {
+        for cell in &mut self.cells[from..to] {
+            *cell = BLANK;
+        }
+    }
+
+    fn character(&mut self, start: usize) -> Option<usize> {
+        let len = match self.pending[start] {
+            b if b < 0x80 => 1,
+            b if b >= 0xf0 => 4,
+            b if b >= 0xe0 => 3,
+            _ => 2,
+        };
+        let bytes = self.pending.get(start..start + len)?;
+        let c = std::str::from_utf8(bytes)
+            .ok()
+            .and_then(|s| s.chars().next())
+            .unwrap_or(std::char::REPLACEMENT_CHARACTER);
+
+        match c {
+            '\r' => self.x = 0,
+            '\n' => self.line_feed(),
+            c => self.put(c),
+        }
+        Some(len)
+    }
+
+    // Like a real terminal, text that goes past the edge wraps onto the next line
+    fn put(&mut self, c: char) {
+        if self.x >= self.width {
+            self.x = 0;
+            self.line_feed();
+        }
+        self.cells[self.y * self.width + self.x] = Cell { c, style: self.style };
+        self.x += 1;
+    }
+
+    // Scrolls everything up at the bottom of the screen
+    fn line_feed(&mut self) {
+        if self.y + 1 < self.height {
+            self.y += 1;
+        } else {
+            self.cells.drain(..self.width);
+            self.cells.extend(std::iter::repeat_n(BLANK, self.width));
+        }
+    }
+}
+
+impl Write for CellGrid {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        self.interpret();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn style_char(style: Style) -> char {
+    match (style.reversed, style.underlined, style.bold) {
+        (false, false, false) => ' ',
+        (true, false, false) => 'r',
+        (false, true, false) => 'u',
+        (false, false, true) => 'b',
+        _ => '*',
+    }
+}
@@ -0,0 +1,94 @@
+use std::io::Write;
+
+use anyhow::Result;
+use crossterm::{cursor, queue, style};
+
+use crate::{
+    api::{Cursor, PageId},
+    app::{Action, NetworkRequest},
+    keybindings::KeyBinding,
+    store::{Store, StoreEvent},
+    views::{BoundingBox, Output, Screen, ScreenId},
+};
+
+#[derive(Debug)]
+pub struct DevicesScreen {
+    cursor: Cursor,
+}
+
+impl DevicesScreen {
+    pub fn new() -> DevicesScreen {
+        DevicesScreen { cursor: Cursor::new() }
+    }
+}
+
+impl Screen for DevicesScreen {
+    fn id(&self) -> ScreenId {
+        ScreenId::Devices
+    }
+
+    fn display(&self, store: &Store, output: &mut Output, bounds: BoundingBox) -> Result<()> {
+        let height = bounds.height as usize;
+
+        let devices = store.devices();
+
+        queue!(
+            output,
+            cursor::MoveTo(bounds.x, bounds.y),
+            style::Print("Devices:"),
+            cursor::MoveToNextLine(1),
+        )?;
+
+        let lines_drawn = 1;
+        self.cursor.queue_draw(
+            output,
+            devices.items().iter(),
+            height.saturating_sub(lines_drawn),
+            |output, d| {
+                let active = if d.is_active() { "*" } else { " " };
+                queue!(
+                    output,
+                    style::Print(format!(
+                        "{} {} ({}) {}%",
+                        active,
+                        d.name(),
+                        d.device_type(),
+                        d.volume_percent(),
+                    )),
+                ).map_err(|e| anyhow!(e))
+            },
+        )?;
+
+        output.flush()?;
+
+        Ok(())
+    }
+
+    fn receive_input(&mut self, store: &Store, input: KeyBinding, bounds: BoundingBox) -> Option<Action> {
+        match input {
+            KeyBinding::Enter => {
+                let id = self.cursor.selected_item(store.devices().items())?.id().to_owned();
+                // Keep playing on the new device if something was playing on the old one
+                let play = store.playback()
+                    .as_ref()
+                    .map(|p| p.is_playing())
+                    .unwrap_or(false);
+
+                store.send_request(NetworkRequest::TransferPlayback(id, play));
+                None
+            }
+            _ => self.cursor.receive_input(store, input, &*store.devices(), bounds.height.saturating_sub(1)),
+        }
+    }
+
+    fn on_store_event(&mut self, _store: &Store, event: &StoreEvent, _bounds: BoundingBox) -> Option<Action> {
+        match event {
+            StoreEvent::PageLoaded(PageId::Devices) => Some(Action::Redraw),
+            _ => None,
+        }
+    }
+
+    fn cursor_mut(&mut self) -> Option<&mut Cursor> {
+        Some(&mut self.cursor)
+    }
+}
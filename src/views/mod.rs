@@ -1,19 +1,39 @@
 use std::fmt::Debug;
 
 use anyhow::Result;
+use crossterm::event::KeyEvent;
 
 use crate::{
+    api::Cursor,
     app::Action,
     keybindings::KeyBinding,
+    store::{Store, StoreEvent},
 };
 
+#[cfg(test)]
+mod cell_grid;
+mod devices_screen;
 mod playlist_screen;
 mod playlists_screen;
 mod popup;
+mod prompt;
+mod search_screen;
+mod status_bar;
+mod terminal;
+mod text_input;
+#[cfg(test)]
+pub use cell_grid::CellGrid;
+pub use devices_screen::DevicesScreen;
 pub use playlist_screen::PlaylistScreen;
 pub use playlists_screen::PlaylistsScreen;
 pub use popup::Popup;
+pub use prompt::Prompt;
+pub use search_screen::SearchScreen;
+pub use status_bar::StatusBar;
+pub use terminal::Output;
+pub use text_input::TextInput;
 
+#[derive(Clone, Copy)]
 pub struct BoundingBox {
     pub x: u16,
     pub y: u16,
@@ -21,8 +41,73 @@ pub struct BoundingBox {
     pub height: u16,
 }
 
+// The part of the terminal screens can draw in, above the status bar
+pub fn screen_bounds(output: &Output) -> Result<BoundingBox> {
+    let (width, height) = output.size()?;
+    Ok(BoundingBox {
+        x: 0,
+        y: 0,
+        width,
+        height: height.saturating_sub(StatusBar::HEIGHT),
+    })
+}
+
+pub fn prompt_bounds(output: &Output) -> Result<BoundingBox> {
+    let (width, height) = output.size()?;
+    Ok(BoundingBox {
+        x: 0,
+        y: height.saturating_sub(1),
+        width,
+        height: 1,
+    })
+}
+
+pub fn status_bar_bounds(output: &Output) -> Result<BoundingBox> {
+    let (width, height) = output.size()?;
+    Ok(BoundingBox {
+        x: 0,
+        y: height.saturating_sub(StatusBar::HEIGHT),
+        width,
+        height: std::cmp::min(height, StatusBar::HEIGHT),
+    })
+}
+
+// Identifies what a screen is showing, so opening the same thing twice can reuse the screen
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScreenId {
+    Playlists,
+    Playlist(String),
+    Devices,
+    Search,
+}
+
 pub trait Screen: Debug {
-    fn display(&self, bounds: BoundingBox) -> Result<()>;
-    fn receive_input(&mut self, input: KeyBinding) -> Option<Action>;
-    fn notify(&mut self, action: Action) -> Option<Action>;
+    fn id(&self) -> ScreenId;
+    fn display(&self, store: &Store, output: &mut Output, bounds: BoundingBox) -> Result<()>;
+    // `bounds` are the same as display gets, for moving around a list a page at a time
+    fn receive_input(&mut self, store: &Store, input: KeyBinding, bounds: BoundingBox) -> Option<Action>;
+
+    // Actions meant for the screen itself, like a search to run
+    fn notify(&mut self, _store: &Store, _action: Action) -> Option<Action> {
+        None
+    }
+
+    // Screens override this to hear about the changes to the store they show
+    fn on_store_event(&mut self, _store: &Store, _event: &StoreEvent, _bounds: BoundingBox) -> Option<Action> {
+        None
+    }
+
+    // The cursor of the list on screen, if there is one, for `/` to search in
+    fn cursor_mut(&mut self) -> Option<&mut Cursor> {
+        None
+    }
+
+    // While this is true the screen gets the raw keys instead of keybindings, for typing into
+    fn is_editing(&self) -> bool {
+        false
+    }
+
+    fn receive_text(&mut self, _store: &Store, _key: KeyEvent) -> Option<Action> {
+        None
+    }
 }
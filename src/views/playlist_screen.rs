@@ -1,15 +1,14 @@
-use std::io::{stdout, Write};
+use std::io::Write;
 
 use anyhow::Result;
-use crossterm::{cursor, queue, style, terminal};
+use crossterm::{cursor, queue, style};
 
 use crate::{
-    send_request,
-    api::Cursor,
+    api::{Cursor, PageId},
     app::{Action, NetworkRequest},
-    data::PLAYLISTS,
     keybindings::KeyBinding,
-    views::{BoundingBox, Screen},
+    store::{Store, StoreEvent},
+    views::{BoundingBox, Output, Screen, ScreenId},
 };
 
 #[derive(Debug)]
@@ -25,17 +24,21 @@ impl PlaylistScreen {
 }
 
 impl Screen for PlaylistScreen {
-    fn display(&self, bounds: BoundingBox) -> Result<()> {
-        let height = terminal::size()?.1 as usize;
+    fn id(&self) -> ScreenId {
+        ScreenId::Playlist(self.playlist_id.clone())
+    }
+
+    fn display(&self, store: &Store, output: &mut Output, bounds: BoundingBox) -> Result<()> {
+        let height = bounds.height as usize;
 
-        let playlists = PLAYLISTS.lock().unwrap();
+        let playlists = store.playlists();
         let playlist = match playlists.get(&self.playlist_id) {
             Some(p) => p,
             None => return Ok(()),
         };
 
         queue!(
-            stdout(),
+            output,
             cursor::MoveTo(bounds.x, bounds.y),
             style::Print(playlist.name()),
             cursor::MoveToNextLine(1),
@@ -43,55 +46,73 @@ impl Screen for PlaylistScreen {
 
         let lines_drawn = 1;
         self.cursor.queue_draw(
+            output,
             playlist.items().iter(),
-            height - lines_drawn,
-            |t| {
+            height.saturating_sub(lines_drawn),
+            |output, t| {
                 let name = if let Some(ref track) = t.track {
                     &track.full_track.name
                 } else {
                     "(N/A)"
                 };
                 queue!(
-                    stdout(),
+                    output,
                     style::Print(name),
                 ).map_err(|e| anyhow!(e))
             },
         )?;
 
-        stdout().flush()?;
+        output.flush()?;
 
         Ok(())
     }
 
-    fn receive_input(&mut self, input: KeyBinding) -> Option<Action> {
+    fn receive_input(&mut self, store: &Store, input: KeyBinding, bounds: BoundingBox) -> Option<Action> {
         match input {
             KeyBinding::InfoPopup => {
-                let playlists = PLAYLISTS.lock().unwrap();
+                let playlists = store.playlists();
                 let tracks = playlists.get(&self.playlist_id)?.tracks();
                 let track = self.cursor.selected_item(&tracks.items()[..])?;
 
                 track.track
                     .as_ref()
-                    .and_then(|p| p.info_popup().ok())
+                    .map(|p| p.info_popup())
                     .map(Action::Popup)
             }
             KeyBinding::Enter => {
-                let playlists = PLAYLISTS.lock().unwrap();
-                let tracks = playlists.get(&self.playlist_id)?.tracks();
-                let track = self.cursor.selected_item(&tracks.items()[..])?;
-                let uri = track.track.as_ref().unwrap().full_track.uri.clone();
+                let playlists = store.playlists();
+                let playlist = playlists.get(&self.playlist_id)?;
+                self.cursor.selected_item(&playlist.items()[..])?.track.as_ref()?;
 
-                send_request(NetworkRequest::PlayUri(uri));
+                store.send_request(NetworkRequest::PlayContext(
+                    playlist.uri().to_owned(),
+                    Some(self.cursor.index() as u32),
+                ));
                 None
             }
             _ => {
-                let playlists = PLAYLISTS.lock().unwrap();
-                self.cursor.receive_input(input, playlists.get(&self.playlist_id)?.tracks(), terminal::size().ok()?.1 - 1)
+                let playlists = store.playlists();
+                self.cursor.receive_input(store, input, playlists.get(&self.playlist_id)?.tracks(), bounds.height.saturating_sub(1))
             }
         }
     }
 
-    fn notify(&mut self, _action: Action) -> Option<Action> {
-        None
+    fn on_store_event(&mut self, store: &Store, event: &StoreEvent, bounds: BoundingBox) -> Option<Action> {
+        let id = match event {
+            StoreEvent::PlaylistAdded(id) | StoreEvent::PageLoaded(PageId::Playlist(id)) => id,
+            _ => return None,
+        };
+        if *id != self.playlist_id {
+            return None;
+        }
+        let height = bounds.height.saturating_sub(1);
+        let playlists = store.playlists();
+        self.cursor
+            .resume(store, playlists.get(&self.playlist_id)?.tracks(), height)
+            .or(Some(Action::Redraw))
+    }
+
+    fn cursor_mut(&mut self) -> Option<&mut Cursor> {
+        Some(&mut self.cursor)
     }
 }
@@ -1,15 +1,14 @@
-use std::io::{stdout, Write};
+use std::io::Write;
 
 use anyhow::Result;
-use crossterm::{cursor, queue, style, terminal};
+use crossterm::{cursor, queue, style};
 
 use crate::{
-    send_request,
-    api::Cursor,
-    app::{Action, NetworkRequest},
-    data::PLAYLIST_SUMMARIES,
+    api::{Cursor, PageId},
+    app::Action,
     keybindings::KeyBinding,
-    views::{BoundingBox, Screen},
+    store::{Store, StoreEvent},
+    views::{BoundingBox, Output, Screen, ScreenId},
 };
 
 #[derive(Debug)]
@@ -24,13 +23,17 @@ impl PlaylistsScreen {
 }
 
 impl Screen for PlaylistsScreen {
-    fn display(&self, bounds: BoundingBox) -> Result<()> {
-        let height = terminal::size()?.1 as usize;
+    fn id(&self) -> ScreenId {
+        ScreenId::Playlists
+    }
+
+    fn display(&self, store: &Store, output: &mut Output, bounds: BoundingBox) -> Result<()> {
+        let height = bounds.height as usize;
 
-        let playlists = PLAYLIST_SUMMARIES.lock().unwrap();
+        let playlists = store.playlist_summaries();
 
         queue!(
-            stdout(),
+            output,
             cursor::MoveTo(bounds.x, bounds.y),
             style::Print("Playlists:"),
             cursor::MoveToNextLine(1),
@@ -38,46 +41,55 @@ impl Screen for PlaylistsScreen {
 
         let lines_drawn = 1;
         self.cursor.queue_draw(
+            output,
             playlists.items().iter(),
-            height - lines_drawn,
-            |p| {
+            height.saturating_sub(lines_drawn),
+            |output, p| {
                 queue!(
-                    stdout(),
+                    output,
                     style::Print(p.name()),
                 ).map_err(|e| anyhow!(e))
             },
         )?;
 
-        stdout().flush()?;
+        output.flush()?;
 
         Ok(())
     }
 
-    fn receive_input(&mut self, input: KeyBinding) -> Option<Action> {
+    fn receive_input(&mut self, store: &Store, input: KeyBinding, bounds: BoundingBox) -> Option<Action> {
         match input {
             KeyBinding::Enter => {
-                let playlists = PLAYLIST_SUMMARIES.lock().unwrap();
+                let playlists = store.playlist_summaries();
                 let id = self.cursor.selected_item(playlists.items())?.id().to_owned();
-                send_request(NetworkRequest::LoadPlaylist(id));
-                None
+                Some(Action::OpenPlaylist(id))
             }
             KeyBinding::InfoPopup => {
-                let playlists = PLAYLIST_SUMMARIES.lock().unwrap();
+                let playlists = store.playlist_summaries();
 
                 self.cursor
                     .selected_item(playlists.items())
-                    .and_then(|p| p.info_popup().ok())
+                    .map(|p| p.info_popup())
                     .map(Action::Popup)
             }
             // TODO: don't just always subtract 1 here...
-            _ => self.cursor.receive_input(input, &*PLAYLIST_SUMMARIES.lock().unwrap(), terminal::size().ok()?.1 - 1),
+            _ => self.cursor.receive_input(store, input, &*store.playlist_summaries(), bounds.height.saturating_sub(1)),
         }
     }
 
-    fn notify(&mut self, action: Action) -> Option<Action> {
-        match action {
-            Action::PlaylistsUpdated => Some(Action::Redraw),
+    fn on_store_event(&mut self, store: &Store, event: &StoreEvent, bounds: BoundingBox) -> Option<Action> {
+        match event {
+            StoreEvent::PageLoaded(PageId::Playlists) => {
+                let height = bounds.height.saturating_sub(1);
+                self.cursor
+                    .resume(store, &*store.playlist_summaries(), height)
+                    .or(Some(Action::Redraw))
+            }
             _ => None,
         }
     }
+
+    fn cursor_mut(&mut self) -> Option<&mut Cursor> {
+        Some(&mut self.cursor)
+    }
 }
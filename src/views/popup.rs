@@ -1,85 +1,80 @@
-use std::io::{stdout, Write};
+use std::io::Write;
 
 use anyhow::Result;
 
 use crossterm::{
     cursor::MoveTo,
-    terminal,
     queue,
     style::Print,
 };
 
+use crate::views::Output;
+
 #[derive(Debug)]
 pub struct Popup {
-    pub width: u16,
-    pub x: u16,
-    pub y: u16,
     pub lines: Vec<String>,
 }
 
 impl Popup {
-    pub fn new(lines: Vec<String>) -> Result<Popup> {
-        let (cols, rows) = terminal::size()?;
-        let x = cols / 4;
-        let y = rows / 4;
-        Ok(Popup {
-            width: x * 2,
-            x,
-            y,
-            lines,
-        })
+    pub fn new(lines: Vec<String>) -> Popup {
+        Popup { lines }
     }
 
-    pub fn display(&self) -> Result<()> {
-        let mut stdout = stdout();
-        
-        print_range(&mut stdout, Some((self.x, self.y)), self.width, '-')?;
+    // Half as wide as the terminal, a quarter of the way in from the top left
+    pub fn display(&self, output: &mut Output) -> Result<()> {
+        let (cols, rows) = output.size()?;
+        let (x, y) = (cols / 4, rows / 4);
+        let width = x * 2;
+
+        print_range(output, Some((x, y)), width, '-')?;
 
         queue!(
-            stdout,
-            MoveTo(self.x, self.y + 1),
+            output,
+            MoveTo(x, y + 1),
             Print('|')
         )?;
-        print_range(&mut stdout, None, self.width - 2, ' ')?;
+        print_range(output, None, width.saturating_sub(2), ' ')?;
         queue!(
-            stdout,
+            output,
             Print('|')
         )?;
 
         for (i, line) in self.lines.iter().enumerate() {
-            // TODO make sure it doesn't overflow
-            let total_padding = self.width - line.len() as u16 - 2;
+            // Lines too long for the popup get cut off
+            let inner = width.saturating_sub(2) as usize;
+            let line: String = line.chars().take(inner).collect();
+            let total_padding = (inner - line.chars().count()) as u16;
             queue!(
-                stdout,
-                MoveTo(self.x, self.y + 2 + i as u16),
+                output,
+                MoveTo(x, y + 2 + i as u16),
                 Print('|'),
             )?;
-            print_range(&mut stdout, None, total_padding / 2, ' ')?;
+            print_range(output, None, total_padding / 2, ' ')?;
             queue!(
-                stdout,
+                output,
                 Print(line),
             )?;
-            print_range(&mut stdout, None, total_padding - total_padding / 2, ' ')?;
+            print_range(output, None, total_padding - total_padding / 2, ' ')?;
             queue!(
-                stdout,
+                output,
                 Print('|'),
             )?;
         }
 
         queue!(
-            stdout,
-            MoveTo(self.x, self.y + 2 + self.lines.len() as u16),
+            output,
+            MoveTo(x, y + 2 + self.lines.len() as u16),
             Print('|')
         )?;
-        print_range(&mut stdout, None, self.width - 2, ' ')?;
+        print_range(output, None, width.saturating_sub(2), ' ')?;
         queue!(
-            stdout,
+            output,
             Print('|')
         )?;
 
-        print_range(&mut stdout, Some((self.x, self.y + 3 + self.lines.len() as u16)), self.width, '-')?;
+        print_range(output, Some((x, y + 3 + self.lines.len() as u16)), width, '-')?;
 
-        stdout.flush()?;
+        output.flush()?;
 
         Ok(())
     }
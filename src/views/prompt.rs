@@ -0,0 +1,36 @@
+use std::io::Write;
+
+use anyhow::Result;
+use crossterm::{cursor, queue, style, terminal};
+
+use crate::views::{BoundingBox, Output, TextInput};
+
+// A line of input at the bottom of the screen, like vim's command line
+#[derive(Debug)]
+pub struct Prompt {
+    prefix: &'static str,
+    pub input: TextInput,
+}
+
+impl Prompt {
+    pub fn new(prefix: &'static str) -> Prompt {
+        Prompt {
+            prefix,
+            input: TextInput::new(),
+        }
+    }
+
+    pub fn display(&self, output: &mut Output, bounds: BoundingBox) -> Result<()> {
+        queue!(
+            output,
+            cursor::MoveTo(bounds.x, bounds.y),
+            terminal::Clear(terminal::ClearType::CurrentLine),
+            style::Print(self.prefix),
+        )?;
+        self.input.queue_draw(output, true)?;
+
+        output.flush()?;
+
+        Ok(())
+    }
+}
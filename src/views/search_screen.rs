@@ -0,0 +1,245 @@
+use std::io::Write;
+
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{KeyCode, KeyEvent},
+    queue,
+    style,
+};
+
+use crate::{
+    api::{Cursor, PageId, SearchKind},
+    app::{Action, NetworkRequest},
+    keybindings::KeyBinding,
+    store::{Store, StoreEvent},
+    views::{BoundingBox, Output, Screen, ScreenId, TextInput},
+};
+
+#[derive(Debug)]
+pub struct SearchScreen {
+    input: TextInput,
+    editing: bool,
+    kind: SearchKind,
+    // One for each of SearchKind::ALL
+    cursors: Vec<Cursor>,
+}
+
+impl SearchScreen {
+    pub fn new() -> SearchScreen {
+        SearchScreen {
+            input: TextInput::new(),
+            editing: true,
+            kind: SearchKind::Tracks,
+            cursors: SearchKind::ALL.iter().map(|_| Cursor::new()).collect(),
+        }
+    }
+
+    fn kind_index(&self) -> usize {
+        SearchKind::ALL.iter().position(|&k| k == self.kind).unwrap()
+    }
+
+    fn cursor(&self) -> &Cursor {
+        &self.cursors[self.kind_index()]
+    }
+
+    fn switch_kind(&mut self, step: isize) {
+        let len = SearchKind::ALL.len() as isize;
+        let i = (self.kind_index() as isize + step + len) % len;
+        self.kind = SearchKind::ALL[i as usize];
+    }
+
+    fn submit(&mut self, store: &Store) {
+        let query = self.input.text();
+        if query.trim().is_empty() {
+            return;
+        }
+
+        self.cursors = SearchKind::ALL.iter().map(|_| Cursor::new()).collect();
+        store.new_search(query.clone());
+        store.send_request(NetworkRequest::Search(query));
+    }
+}
+
+impl Screen for SearchScreen {
+    fn id(&self) -> ScreenId {
+        ScreenId::Search
+    }
+
+    fn display(&self, store: &Store, output: &mut Output, bounds: BoundingBox) -> Result<()> {
+        let height = bounds.height as usize;
+
+        queue!(
+            output,
+            cursor::MoveTo(bounds.x, bounds.y),
+            style::Print("Search: "),
+        )?;
+        self.input.queue_draw(output, self.editing)?;
+        queue!(output, cursor::MoveToNextLine(1))?;
+
+        for &kind in SearchKind::ALL.iter() {
+            if kind == self.kind {
+                queue!(output, style::SetAttribute(style::Attribute::Reverse))?;
+            }
+            queue!(
+                output,
+                style::Print(format!(" {} ", kind.name())),
+                style::SetAttribute(style::Attribute::Reset),
+                style::Print(" "),
+            )?;
+        }
+        queue!(output, cursor::MoveToNextLine(1))?;
+
+        let results = store.search_results();
+        let lines_drawn = 2;
+        let height = height.saturating_sub(lines_drawn);
+        let print = |output: &mut Output, s: String| queue!(output, style::Print(s)).map_err(|e| anyhow!(e));
+        match self.kind {
+            SearchKind::Tracks => self.cursor().queue_draw(
+                output,
+                results.tracks.items().iter(),
+                height,
+                |output, t| print(output, format!("{} - {}", t.full_track.name, t.artists())),
+            )?,
+            SearchKind::Albums => self.cursor().queue_draw(
+                output,
+                results.albums.items().iter(),
+                height,
+                |output, a| print(output, format!("{} - {}", a.name(), a.artists())),
+            )?,
+            SearchKind::Artists => self.cursor().queue_draw(
+                output,
+                results.artists.items().iter(),
+                height,
+                |output, a| print(output, a.name().to_owned()),
+            )?,
+            SearchKind::Playlists => self.cursor().queue_draw(
+                output,
+                results.playlists.items().iter(),
+                height,
+                |output, p| print(output, p.name().to_owned()),
+            )?,
+        }
+
+        output.flush()?;
+
+        Ok(())
+    }
+
+    fn receive_input(&mut self, store: &Store, input: KeyBinding, bounds: BoundingBox) -> Option<Action> {
+        let results = store.search_results();
+        match input {
+            KeyBinding::Search => {
+                self.editing = true;
+                Some(Action::Redraw)
+            }
+            KeyBinding::NextGroup => {
+                self.switch_kind(1);
+                Some(Action::Redraw)
+            }
+            KeyBinding::PrevGroup => {
+                self.switch_kind(-1);
+                Some(Action::Redraw)
+            }
+            KeyBinding::Enter => {
+                let cursor = self.cursor();
+                match self.kind {
+                    SearchKind::Tracks => {
+                        let track = cursor.selected_item(results.tracks.items())?;
+                        store.send_request(NetworkRequest::PlayUri(track.full_track.uri.clone()));
+                        None
+                    }
+                    SearchKind::Albums => {
+                        let uri = cursor.selected_item(results.albums.items())?.uri()?;
+                        store.send_request(NetworkRequest::PlayContext(uri.to_owned(), Some(0)));
+                        None
+                    }
+                    SearchKind::Artists => {
+                        let uri = cursor.selected_item(results.artists.items())?.uri();
+                        // Spotify doesn't allow an offset for artists
+                        store.send_request(NetworkRequest::PlayContext(uri.to_owned(), None));
+                        None
+                    }
+                    SearchKind::Playlists => {
+                        let id = cursor.selected_item(results.playlists.items())?.id();
+                        Some(Action::OpenPlaylist(id.to_owned()))
+                    }
+                }
+            }
+            KeyBinding::InfoPopup => {
+                let cursor = self.cursor();
+                let popup = match self.kind {
+                    SearchKind::Tracks => cursor.selected_item(results.tracks.items())?.info_popup(),
+                    SearchKind::Albums => cursor.selected_item(results.albums.items())?.info_popup(),
+                    SearchKind::Artists => cursor.selected_item(results.artists.items())?.info_popup(),
+                    SearchKind::Playlists => cursor.selected_item(results.playlists.items())?.info_popup(),
+                };
+                Some(Action::Popup(popup))
+            }
+            _ => {
+                let height = bounds.height.saturating_sub(2);
+                let i = self.kind_index();
+                let cursor = &mut self.cursors[i];
+                match self.kind {
+                    SearchKind::Tracks => cursor.receive_input(store, input, &results.tracks, height),
+                    SearchKind::Albums => cursor.receive_input(store, input, &results.albums, height),
+                    SearchKind::Artists => cursor.receive_input(store, input, &results.artists, height),
+                    SearchKind::Playlists => cursor.receive_input(store, input, &results.playlists, height),
+                }
+            }
+        }
+    }
+
+    fn notify(&mut self, store: &Store, action: Action) -> Option<Action> {
+        match action {
+            Action::SubmitSearch(query) => {
+                self.input.set_text(&query);
+                self.editing = false;
+                self.submit(store);
+                Some(Action::Redraw)
+            }
+            _ => None,
+        }
+    }
+
+    fn on_store_event(&mut self, store: &Store, event: &StoreEvent, bounds: BoundingBox) -> Option<Action> {
+        match event {
+            StoreEvent::PageLoaded(PageId::Search { .. }) => {
+                let height = bounds.height.saturating_sub(2);
+                let results = store.search_results();
+                let i = self.kind_index();
+                let cursor = &mut self.cursors[i];
+                match self.kind {
+                    SearchKind::Tracks => cursor.resume(store, &results.tracks, height),
+                    SearchKind::Albums => cursor.resume(store, &results.albums, height),
+                    SearchKind::Artists => cursor.resume(store, &results.artists, height),
+                    SearchKind::Playlists => cursor.resume(store, &results.playlists, height),
+                }.or(Some(Action::Redraw))
+            }
+            _ => None,
+        }
+    }
+
+    fn cursor_mut(&mut self) -> Option<&mut Cursor> {
+        let i = self.kind_index();
+        Some(&mut self.cursors[i])
+    }
+
+    fn is_editing(&self) -> bool {
+        self.editing
+    }
+
+    fn receive_text(&mut self, store: &Store, key: KeyEvent) -> Option<Action> {
+        match key.code {
+            KeyCode::Esc => self.editing = false,
+            KeyCode::Enter => {
+                self.editing = false;
+                self.submit(store);
+            }
+            _ => {
+                self.input.handle_key(key);
+            }
+        }
+        Some(Action::Redraw)
+    }
+}
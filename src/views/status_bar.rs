@@ -0,0 +1,139 @@
+use std::io::Write;
+
+use anyhow::Result;
+use crossterm::{cursor, queue, style, terminal};
+use rspotify::senum::RepeatState;
+
+use crate::{
+    api::format_time,
+    store::Store,
+    views::{BoundingBox, Output},
+};
+
+#[derive(Debug)]
+pub struct StatusBar;
+
+impl StatusBar {
+    pub const HEIGHT: u16 = 2;
+
+    pub fn new() -> StatusBar {
+        StatusBar
+    }
+
+    pub fn display(&self, store: &Store, output: &mut Output, bounds: BoundingBox) -> Result<()> {
+        let width = bounds.width as usize;
+
+        for i in 0..bounds.height {
+            queue!(
+                output,
+                cursor::MoveTo(bounds.x, bounds.y + i),
+                terminal::Clear(terminal::ClearType::CurrentLine),
+            )?;
+        }
+
+        if bounds.height == 0 {
+            output.flush()?;
+            return Ok(());
+        }
+
+        let playback = store.playback();
+        let playback = match *playback {
+            Some(ref p) => p,
+            None => {
+                queue!(
+                    output,
+                    cursor::MoveTo(bounds.x, bounds.y),
+                    style::Print("Nothing playing"),
+                )?;
+                output.flush()?;
+                return Ok(());
+            }
+        };
+
+        let state = if playback.is_playing() { ">" } else { "||" };
+        let track = match (playback.title(), playback.artists()) {
+            (Some(title), Some(artists)) => format!("{} {} - {}", state, title, artists),
+            (Some(title), None) => format!("{} {}", state, title),
+            _ => state.to_owned(),
+        };
+        let volume = match playback.volume_percent() {
+            0 => "muted".to_owned(),
+            v => format!("{}%", v),
+        };
+        let device = format!(" [{} {}]", playback.device_name(), volume);
+        let track_width = width.saturating_sub(device.chars().count());
+
+        queue!(
+            output,
+            cursor::MoveTo(bounds.x, bounds.y),
+            style::SetAttribute(style::Attribute::Bold),
+            style::Print(truncate(&track, track_width)),
+            style::SetAttribute(style::Attribute::Reset),
+            cursor::MoveTo(bounds.x + (width.saturating_sub(device.chars().count())) as u16, bounds.y),
+            style::Print(truncate(&device, width)),
+        )?;
+
+        // The progress bar goes on the second line, if the terminal has room for one
+        if bounds.height < 2 {
+            output.flush()?;
+            return Ok(());
+        }
+
+        let progress = playback.progress_ms();
+        let duration = playback.duration_ms();
+        let elapsed = format_time(progress);
+        let total = format_time(duration);
+        let mut modes = String::new();
+        if playback.shuffle() {
+            modes.push_str(" [shuffle]");
+        }
+        match playback.repeat() {
+            RepeatState::Context => modes.push_str(" [repeat]"),
+            RepeatState::Track => modes.push_str(" [repeat one]"),
+            RepeatState::Off => {}
+        }
+        // The 4 is for the spaces and brackets around the bar
+        let bar_width = width.saturating_sub(elapsed.len() + total.len() + modes.len() + 4);
+        let filled = if duration == 0 {
+            0
+        } else {
+            bar_width * progress as usize / duration as usize
+        };
+
+        queue!(
+            output,
+            cursor::MoveTo(bounds.x, bounds.y + 1),
+            style::Print(format!(
+                "{} [{}{}] {}{}",
+                elapsed,
+                "=".repeat(filled),
+                " ".repeat(bar_width - filled),
+                total,
+                modes,
+            )),
+        )?;
+
+        output.flush()?;
+
+        Ok(())
+    }
+
+    // Over the bottom line, where the prompt would be
+    pub fn display_message(&self, output: &mut Output, bounds: BoundingBox, message: &str) -> Result<()> {
+        queue!(
+            output,
+            cursor::MoveTo(bounds.x, bounds.y),
+            terminal::Clear(terminal::ClearType::CurrentLine),
+            style::SetAttribute(style::Attribute::Bold),
+            style::Print(truncate(message, bounds.width as usize)),
+            style::SetAttribute(style::Attribute::Reset),
+        )?;
+        output.flush()?;
+
+        Ok(())
+    }
+}
+
+fn truncate(s: &str, width: usize) -> String {
+    s.chars().take(width).collect()
+}
@@ -0,0 +1,48 @@
+// Where drawing goes. Normally that's the real terminal, but tests draw into a CellGrid
+// instead so they can check what's on screen. Each App has its own, and passes it to whatever
+// it draws
+
+use std::io::{self, stdout, Write};
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use crossterm::terminal;
+
+#[cfg(test)]
+use crate::views::CellGrid;
+
+pub enum Output {
+    Terminal,
+    #[cfg(test)]
+    Grid(Arc<Mutex<CellGrid>>),
+}
+
+impl Output {
+    // Columns and rows, like terminal::size
+    pub fn size(&self) -> Result<(u16, u16)> {
+        match self {
+            Output::Terminal => Ok(terminal::size()?),
+            #[cfg(test)]
+            Output::Grid(grid) => Ok(grid.lock().unwrap().size()),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Terminal => stdout().write(buf),
+            #[cfg(test)]
+            Output::Grid(grid) => grid.lock().unwrap().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Terminal => stdout().flush(),
+            #[cfg(test)]
+            Output::Grid(grid) => grid.lock().unwrap().flush(),
+        }
+    }
+}
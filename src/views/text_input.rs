@@ -0,0 +1,109 @@
+use std::io::Write;
+
+use anyhow::Result;
+use crossterm::{
+    event::{KeyCode, KeyEvent, KeyModifiers},
+    queue,
+    style,
+};
+
+use crate::views::Output;
+
+// A single line of editable text, with readline style keys
+#[derive(Debug)]
+pub struct TextInput {
+    chars: Vec<char>,
+    cursor: usize,
+}
+
+impl TextInput {
+    pub fn new() -> TextInput {
+        TextInput {
+            chars: Vec::new(),
+            cursor: 0,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.chars = text.chars().collect();
+        self.cursor = self.chars.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    // Returns false for keys that don't edit the text, like enter and escape
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::Char('e') if ctrl => self.cursor = self.chars.len(),
+            KeyCode::Char('u') if ctrl => {
+                self.chars.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            KeyCode::Char('w') if ctrl => {
+                let mut start = self.cursor;
+                while start > 0 && self.chars[start - 1] == ' ' {
+                    start -= 1;
+                }
+                while start > 0 && self.chars[start - 1] != ' ' {
+                    start -= 1;
+                }
+                self.chars.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            KeyCode::Char(_) if ctrl => return false,
+            KeyCode::Char(c) => {
+                self.chars.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    self.chars.remove(self.cursor);
+                }
+            }
+            KeyCode::Delete => {
+                if self.cursor < self.chars.len() {
+                    self.chars.remove(self.cursor);
+                }
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = std::cmp::min(self.chars.len(), self.cursor + 1),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.chars.len(),
+            _ => return false,
+        }
+        true
+    }
+
+    // The character under the cursor is drawn reversed, since the real cursor is hidden
+    pub fn queue_draw(&self, output: &mut Output, show_cursor: bool) -> Result<()> {
+        let before: String = self.chars[..self.cursor].iter().collect();
+        queue!(output, style::Print(before))?;
+
+        if !show_cursor {
+            let after: String = self.chars[self.cursor..].iter().collect();
+            queue!(output, style::Print(after))?;
+            return Ok(());
+        }
+
+        let under = self.chars.get(self.cursor).copied().unwrap_or(' ');
+        let after: String = self.chars.iter().skip(self.cursor + 1).collect();
+        queue!(
+            output,
+            style::SetAttribute(style::Attribute::Reverse),
+            style::Print(under),
+            style::SetAttribute(style::Attribute::Reset),
+            style::Print(after),
+        )?;
+
+        Ok(())
+    }
+}